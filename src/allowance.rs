@@ -0,0 +1,146 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+use serde::Serialize;
+
+use crate::{args::AllowanceArgs, config::{self, AllowanceConfig}, holidays::{self, Holiday}, roster::CalendarEvent, util, xlsx};
+
+
+/// Hours worked in each enhancement bucket over a month
+#[derive(Debug, Default, Serialize)]
+pub struct HourBuckets {
+    pub weekday_day: f64,
+    pub weekday_night: f64,
+    pub saturday: f64,
+    pub sunday: f64,
+    pub bank_holiday: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bucket {
+    WeekdayDay,
+    WeekdayNight,
+    Saturday,
+    Sunday,
+    BankHoliday,
+}
+
+pub fn run(args: &AllowanceArgs) -> anyhow::Result<()> {
+    let config = config::load(args.config.as_deref())?;
+    let roster = xlsx::open_roster(&args.roster)?;
+    let holidays = holidays::load(&config.holidays, roster.first_day_of_month.year())?;
+
+    let people = if args.all {
+        roster.people(args.watch.as_deref())?
+    } else {
+        let selected_name_index = util::select_name_from_user(&roster, "Whose allowances do you wish to calculate?");
+        vec![roster.names[selected_name_index].clone()]
+    };
+
+    let name_width = people.iter().map(|(_, name)| name.len()).max().unwrap_or(0).max(4);
+    let mut lines = Vec::with_capacity(people.len());
+    for (row, name) in &people {
+        let events = util::get_calendar_events_for_person(&roster, *row, name, !args.all)?;
+        let buckets = split_into_buckets(&config.allowance, &events, &holidays);
+        let total = amount(&config.allowance, &buckets);
+        lines.push(format!(
+            "{:<name_width$}  {:>8.2}  {:>8.2}  {:>8.2}  {:>8.2}  {:>8.2}  {:>9.2}",
+            name, buckets.weekday_day, buckets.weekday_night, buckets.saturday, buckets.sunday, buckets.bank_holiday, total,
+        ));
+    }
+
+    println!("Allowances for {}", roster.first_day_of_month.format("%B %Y"));
+    println!("{:<name_width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>9}", "Name", "Day", "Night", "Saturday", "Sunday", "Bank hol", "Allowance");
+    for line in lines {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Split each timed event into hours in each bucket
+pub fn split_into_buckets(config: &AllowanceConfig, events: &[CalendarEvent], holidays: &[Holiday]) -> HourBuckets {
+    let mut buckets = HourBuckets::default();
+    for event in events {
+        let CalendarEvent::Normal { start, end, .. } = event else {
+            continue;
+        };
+        let mut current = *start;
+        while current < *end {
+            let segment_end = next_boundary(config, current).min(*end);
+            let hours = (segment_end - current).num_minutes() as f64 / 60.0;
+            match bucket(config, current, holidays) {
+                Bucket::WeekdayDay => buckets.weekday_day += hours,
+                Bucket::WeekdayNight => buckets.weekday_night += hours,
+                Bucket::Saturday => buckets.saturday += hours,
+                Bucket::Sunday => buckets.sunday += hours,
+                Bucket::BankHoliday => buckets.bank_holiday += hours,
+            }
+            current = segment_end;
+        }
+    }
+    buckets
+}
+
+pub fn amount(config: &AllowanceConfig, buckets: &HourBuckets) -> f64 {
+    buckets.weekday_day * config.weekday_day_rate
+        + buckets.weekday_night * config.weekday_night_rate
+        + buckets.saturday * config.saturday_rate
+        + buckets.sunday * config.sunday_rate
+        + buckets.bank_holiday * config.bank_holiday_rate
+}
+
+fn bucket(config: &AllowanceConfig, at: NaiveDateTime, holidays: &[Holiday]) -> Bucket {
+    if holidays::find(holidays, at.date()).is_some() {
+        return Bucket::BankHoliday;
+    }
+    match at.weekday() {
+        Weekday::Sun => Bucket::Sunday,
+        Weekday::Sat => Bucket::Saturday,
+        _ if is_night(config, at.time()) => Bucket::WeekdayNight,
+        _ => Bucket::WeekdayDay,
+    }
+}
+
+fn is_night(config: &AllowanceConfig, time: NaiveTime) -> bool {
+    if config.night_start <= config.night_end {
+        time >= config.night_start && time < config.night_end
+    } else {
+        time >= config.night_start || time < config.night_end
+    }
+}
+
+/// The next time after `at` at which the bucket could change: midnight, or the start or end of the night
+fn next_boundary(config: &AllowanceConfig, at: NaiveDateTime) -> NaiveDateTime {
+    let midnight = at.date().and_time(NaiveTime::MIN) + TimeDelta::days(1);
+    [config.night_start, config.night_end].iter()
+        .map(|time| at.date().and_time(*time))
+        .filter(|boundary| *boundary > at)
+        .fold(midnight, NaiveDateTime::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{holidays::Region, roster::tests::events};
+
+    /// Duties from 1 January 2025, which is New Year's Day
+    fn buckets(config: &AllowanceConfig, codes: &[&str]) -> HourBuckets {
+        split_into_buckets(config, &events(codes), &holidays::bank_holidays(Region::EnglandAndWales, 2025))
+    }
+
+    #[test]
+    fn hours_are_split_at_night_and_midnight() {
+        let buckets = buckets(&AllowanceConfig::default(), &["M", "D4", "N", "//", "N"]);
+        // D4 on Thursday runs past 20:00, Friday's night runs into Saturday and Sunday's into Monday
+        assert_eq!(buckets.weekday_day, 5.0 + 0.5);
+        assert_eq!(buckets.weekday_night, 2.5 + 2.0 + 6.0);
+        assert_eq!(buckets.saturday, 6.5);
+        assert_eq!(buckets.sunday, 2.0);
+        assert_eq!(buckets.bank_holiday, 7.0);
+    }
+
+    #[test]
+    fn allowance_uses_each_bucket_rate() {
+        let config = AllowanceConfig { weekday_night_rate: 2.0, saturday_rate: 3.0, sunday_rate: 4.0, bank_holiday_rate: 5.0, ..AllowanceConfig::default() };
+        let buckets = buckets(&config, &["M", "D4", "N", "//", "N"]);
+        assert_eq!(amount(&config, &buckets), 10.5 * 2.0 + 6.5 * 3.0 + 2.0 * 4.0 + 7.0 * 5.0);
+    }
+}
@@ -1,20 +1,67 @@
 use std::path::PathBuf;
 
-use clap::{Parser, ValueEnum};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
 
+/// Convert a monthly roster spreadsheet into a calendar
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub roster: Option<RosterArgs>,
+
+    #[command(flatten)]
+    pub generate: Option<GenerateArgs>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Show a person's month as a calendar in the terminal
+    Show(ShowArgs),
+    /// Check rosters against the working-time rules in the config file
+    Check(CheckArgs),
+    /// Total up hours, shifts, leave and days off
+    Stats(StatsArgs),
+    /// Calculate unsocial-hours allowances using the rates in the config file
+    Allowance(AllowanceArgs),
+    /// Record leave in the leave ledger and report balances
+    Leave(LeaveArgs),
+    /// Write a calendar of the whole team, with one event per shift listing everyone on it
+    Team(TeamArgs),
+    /// Count how many people are on each shift each day and highlight shifts below the minimums in the config file
+    Coverage(CoverageArgs),
+    /// Find colleagues to swap a shift with
+    Swap(SwapArgs),
+    /// List the days that two or more people are all off
+    Common(CommonArgs),
+    /// Suggest colleagues to travel in with, based on shift start and end times
+    Carpool(CarpoolArgs),
+}
+
+// Arguments for locating and reading the roster spreadsheet
+#[derive(clap::Args, Debug)]
+pub struct RosterArgs {
     /// The .xlsx file that contain's the monthly roster
     pub xlsx_path: PathBuf,
 
-    /// Override the calendar month. If this is not specified, we will attempt to extract the month from the file name
+    /// Override the calendar month. If this is not specified, it is taken from the first of these that gives it: dates in
+    /// the header row, title cells above it, sheet names, the file name and the document properties. It is then checked
+    /// against the weekday row, if there is one. Required if none of these give the month, or if each that does disagrees
+    /// with another equally reliable one
     #[clap(short, value_enum)]
     pub month: Option<Month>,
 
-    /// Override the year. If this is not specified, we will attempt to extract the year from the file name
+    /// Override the year. If this is not specified, it is found in the same way as the month. Required if nothing in the
+    /// roster or its file name gives the year
     #[clap(short)]
     pub year: Option<u16>,
+}
 
+#[derive(clap::Args, Debug)]
+pub struct GenerateArgs {
     /// Override the calendar name. If not specified, it will default to "My Calendar"
     #[clap(short)]
     pub name: Option<String>,
@@ -23,13 +70,293 @@ pub struct Args {
     #[clap(short)]
     pub email: Option<String>,
 
-    /// How long before the event you wish to be notified / e-mailed. A number followed by m, h or d for minutes, hours or days
+    /// How long before the event you wish to be notified / e-mailed. A number followed by m, h or d for minutes, hours or days.
+    /// For Outlook CSV output, setting this (or -e) switches reminders on
     #[clap(short)]
     pub reminder_time: Option<String>,
 
-    /// The path to write the output .ics file to
+    /// The format of the output file
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Ics)]
+    pub format: OutputFormat,
+
+    /// The path to write the output file to
+    #[arg(short)]
+    pub output: PathBuf,
+
+    /// Add an all-day event for each bank holiday in the month
+    #[clap(long)]
+    pub holiday_markers: bool,
+
+    /// Add the name of the bank holiday to shifts and all-day events that fall on one
+    #[clap(long)]
+    pub holiday_names: bool,
+
+    /// List the colleagues on duty at the same time in each shift's description
+    #[clap(long)]
+    pub colleagues: bool,
+
+    /// Suggest colleagues to travel in with in each shift's description. See the carpool subcommand
+    #[clap(long)]
+    pub carpool: bool,
+
+    /// The config file. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ShowArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Don't colour the output. Colour is also disabled if NO_COLOR is set or the output isn't a terminal
+    #[clap(long)]
+    pub no_colour: bool,
+}
+
+
+#[derive(clap::Args, Debug)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Check everyone on the roster rather than choosing one person
+    #[clap(short, long)]
+    pub all: bool,
+
+    /// With --all, only include the people on this watch, e.g. B
+    #[clap(short, long, requires = "all")]
+    pub watch: Option<String>,
+
+    /// The config file containing the rules. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+
+#[derive(clap::Args, Debug)]
+pub struct StatsArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Total up everyone on the roster rather than choosing one person
+    #[clap(short, long)]
+    pub all: bool,
+
+    /// With --all, only include the people on this watch, e.g. B
+    #[clap(short, long, requires = "all")]
+    pub watch: Option<String>,
+
+    /// How to print the statistics
+    #[clap(short, long, value_enum, default_value_t = StatsFormat::Table)]
+    pub format: StatsFormat,
+
+    /// The config file. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+
+#[derive(clap::Args, Debug)]
+pub struct AllowanceArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Calculate for everyone on the roster rather than choosing one person
+    #[clap(short, long)]
+    pub all: bool,
+
+    /// With --all, only include the people on this watch, e.g. B
+    #[clap(short, long, requires = "all")]
+    pub watch: Option<String>,
+
+    /// The config file containing the rates. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+
+#[derive(clap::Args, Debug)]
+pub struct LeaveArgs {
+    #[command(subcommand)]
+    pub command: LeaveCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LeaveCommand {
+    /// Record the leave on a roster in the ledger. Re-recording a month replaces what was there
+    Record(LeaveRecordArgs),
+    /// Show how much leave has been used and remains in the leave year
+    Balance(LeaveBalanceArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct LeaveRecordArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Record everyone on the roster rather than choosing one person
+    #[clap(short, long)]
+    pub all: bool,
+
+    /// With --all, only include the people on this watch, e.g. B
+    #[clap(short, long, requires = "all")]
+    pub watch: Option<String>,
+
+    /// The config file. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct LeaveBalanceArgs {
+    /// Only show this person, by name as it appears on the roster
+    #[clap(short, long)]
+    pub name: Option<String>,
+
+    /// Report on the leave year containing this date (YYYY-MM-DD). Defaults to today
+    #[clap(short, long)]
+    pub date: Option<NaiveDate>,
+
+    /// The config file. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum TeamFormat {
+    Ics,
+    Jcal,
+    Xcal,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TeamArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Only include the people on this watch, e.g. B
+    #[clap(short, long)]
+    pub watch: Option<String>,
+
+    /// Override the calendar name. If not specified, it will default to "My Calendar"
+    #[clap(short)]
+    pub name: Option<String>,
+
+    /// The format of the output file
+    #[clap(short, long, value_enum, default_value_t = TeamFormat::Ics)]
+    pub format: TeamFormat,
+
+    /// The path to write the output file to
+    #[arg(short)]
+    pub output: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CoverageArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Only count the people on this watch, e.g. B
+    #[clap(short, long)]
+    pub watch: Option<String>,
+
+    /// How to write the grid
+    #[clap(short, long, value_enum, default_value_t = CoverageFormat::Table)]
+    pub format: CoverageFormat,
+
+    /// The path to write the grid to. Required for HTML, otherwise the grid is printed
+    #[arg(short, required_if_eq("format", "html"))]
+    pub output: Option<PathBuf>,
+
+    /// Don't colour the table. Colour is also disabled if NO_COLOR is set or the output isn't a terminal
+    #[clap(long)]
+    pub no_colour: bool,
+
+    /// The config file containing the minimums. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SwapArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Whose shift to swap, by name as it appears on the roster. If not given, you will be asked
+    #[clap(short, long)]
+    pub name: Option<String>,
+
+    /// The date of the shift to give away (YYYY-MM-DD)
+    #[clap(short, long)]
+    pub date: NaiveDate,
+
+    /// Only look for colleagues on this watch, e.g. B
+    #[clap(short, long)]
+    pub watch: Option<String>,
+
+    /// The config file containing the rules. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CommonArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// The people to compare, by name as it appears on the roster. Give this once for each person
+    #[clap(short, long = "name", required = true)]
+    pub names: Vec<String>,
+
+    /// Write the days off to this .ics file as all-day events rather than listing them
     #[arg(short)]
-    pub output_ics: PathBuf
+    pub output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CarpoolArgs {
+    #[command(flatten)]
+    pub roster: RosterArgs,
+
+    /// Whose shifts to match, by name as it appears on the roster. If not given, you will be asked
+    #[clap(short, long)]
+    pub name: Option<String>,
+
+    /// The config file containing the tolerance and home areas. Defaults to roster-to-ics.toml in the current directory, if present
+    #[clap(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CoverageFormat {
+    Table,
+    Csv,
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum StatsFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// iCalendar (.ics)
+    Ics,
+    /// iCalendar as JSON (jCal, RFC 7265)
+    Jcal,
+    /// iCalendar as XML (xCal, RFC 6321)
+    Xcal,
+    /// The parsed roster and generated events as JSON
+    Json,
+    /// CSV for importing into Google Calendar
+    GoogleCsv,
+    /// CSV for importing into Outlook
+    OutlookCsv,
+    /// A printable month view as a single HTML file
+    Html,
 }
 
 
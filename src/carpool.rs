@@ -0,0 +1,82 @@
+//! Colleagues who start and finish at the same time as a person, and so could travel in together
+
+use chrono::NaiveDateTime;
+
+use crate::{args::CarpoolArgs, config::{self, CarpoolConfig}, roster::CalendarEvent, team, util, xlsx::{self, RosterSheet}};
+
+
+/// A colleague whose shift matches, and whether they live in the same area
+struct Suggestion<'a> {
+    name: &'a str,
+    area: Option<&'a str>,
+    same_area: bool,
+}
+
+pub fn run(args: &CarpoolArgs) -> anyhow::Result<()> {
+    let config = config::load(args.config.as_deref())?;
+    let roster = xlsx::open_roster(&args.roster)?;
+    let (row, name) = match &args.name {
+        Some(name) => roster.find_name(name)?,
+        None => roster.names[util::select_name_from_user(&roster, "Whose shifts do you wish to match?")].clone(),
+    };
+    let events = util::get_calendar_events_for_person(&roster, row, &name, false)?;
+    let colleague_events = team::colleague_events(&roster, row);
+
+    println!("Carpool suggestions for {name} in {}", roster.first_day_of_month.format("%B %Y"));
+    for event in &events {
+        let CalendarEvent::Normal { name: shift, start, end, .. } = event else {
+            continue;
+        };
+        let suggestions = suggestions(&config.carpool, &name, *start, *end, &colleague_events);
+        let list = if suggestions.is_empty() {
+            String::from("nobody")
+        } else {
+            suggestions.iter().map(describe).collect::<Vec<_>>().join(", ")
+        };
+        println!("  {} {} {}-{}: {list}", start.format("%a %d"), shift, start.format("%H%M"), end.format("%H%M"));
+    }
+    Ok(())
+}
+
+/// Add the colleagues to travel with to the description of each timed event
+pub fn add_suggestions(config: &CarpoolConfig, roster: &RosterSheet, selected_row: usize, selected_name: &str, events: &mut [CalendarEvent]) {
+    let colleague_events = team::colleague_events(roster, selected_row);
+    for event in events {
+        let CalendarEvent::Normal { start, end, .. } = event else {
+            continue;
+        };
+        let suggestions = suggestions(config, selected_name, *start, *end, &colleague_events);
+        if !suggestions.is_empty() {
+            event.append_description(&format!("Carpool: {}", suggestions.iter().map(describe).collect::<Vec<_>>().join(", ")));
+        }
+    }
+}
+
+/// Colleagues whose start and end times are both within the tolerance, those in the same home area first
+fn suggestions<'a>(config: &'a CarpoolConfig, name: &str, start: NaiveDateTime, end: NaiveDateTime, colleague_events: &'a [(&String, CalendarEvent)]) -> Vec<Suggestion<'a>> {
+    let tolerance = i64::from(config.tolerance_minutes);
+    let my_area = config.home_areas.get(name);
+    let mut suggestions: Vec<Suggestion> = colleague_events.iter()
+        .filter(|(_, event)| match event {
+            CalendarEvent::Normal { start: other_start, end: other_end, .. } => other_start < other_end
+                && (*other_start - start).num_minutes().abs() <= tolerance
+                && (*other_end - end).num_minutes().abs() <= tolerance,
+            _ => false,
+        })
+        .map(|(colleague, _)| {
+            let area = config.home_areas.get(*colleague);
+            Suggestion { name: colleague, area: area.map(String::as_str), same_area: my_area.is_some() && area == my_area }
+        })
+        .collect();
+    // Stable, so the roster order is kept within each group
+    suggestions.sort_by_key(|suggestion| !suggestion.same_area);
+    suggestions
+}
+
+fn describe(suggestion: &Suggestion) -> String {
+    match (suggestion.same_area, suggestion.area) {
+        (true, _) => format!("{} (same area)", suggestion.name),
+        (false, Some(area)) => format!("{} ({area})", suggestion.name),
+        (false, None) => suggestion.name.to_string(),
+    }
+}
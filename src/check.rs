@@ -0,0 +1,217 @@
+use anyhow::bail;
+use chrono::{Days, NaiveDate, NaiveDateTime, TimeDelta};
+
+use crate::{args::CheckArgs, config::{self, RuleConfig}, roster::CalendarEvent, util, xlsx};
+
+const DATE_FORMAT_STRING: &str = "%d %B";
+
+
+/// A breach of one of the working-time rules
+pub struct Violation {
+    pub first_date: NaiveDate,
+    pub last_date: NaiveDate,
+    pub message: String,
+}
+
+pub fn run(args: &CheckArgs) -> anyhow::Result<()> {
+    let config = config::load(args.config.as_deref())?;
+    let roster = xlsx::open_roster(&args.roster)?;
+
+    let people = if args.all {
+        roster.people(args.watch.as_deref())?
+    } else {
+        let selected_name_index = util::select_name_from_user(&roster, "Whose roster do you wish to check?");
+        vec![roster.names[selected_name_index].clone()]
+    };
+
+    let mut num_violations = 0;
+    for (row, name) in &people {
+        let events = util::get_calendar_events_for_person(&roster, *row, name, !args.all)?;
+        let violations = check_events(&config.rules, &events);
+        if violations.is_empty() {
+            continue;
+        }
+        num_violations += violations.len();
+        println!("{name}");
+        for violation in violations {
+            let first = violation.first_date.format(DATE_FORMAT_STRING);
+            let last = violation.last_date.format(DATE_FORMAT_STRING);
+            if violation.first_date == violation.last_date {
+                println!("  {first}: {}", violation.message);
+            } else {
+                println!("  {first} - {last}: {}", violation.message);
+            }
+        }
+    }
+
+    if num_violations > 0 {
+        bail!("{num_violations} working time rule violation(s) found");
+    }
+    println!("No working time rule violations found.");
+    Ok(())
+}
+
+/// Check a person's events against the working-time rules
+pub fn check_events(rules: &RuleConfig, events: &[CalendarEvent]) -> Vec<Violation> {
+    let mut duties: Vec<(NaiveDateTime, NaiveDateTime)> = events.iter().filter_map(|event| match event {
+        CalendarEvent::Normal { start, end, .. } if start < end => Some((*start, *end)),
+        _ => None,
+    }).collect();
+    duties.sort();
+
+    let mut violations = Vec::new();
+
+    // Rest between duties, with a longer minimum after the last of a run of nights
+    for pair in duties.windows(2) {
+        let (previous, next) = (pair[0], pair[1]);
+        let rest = hours(next.0 - previous.1);
+        let after_nights = is_night(previous) && !(is_night(next) && is_next_day(previous, next));
+        let (minimum, description) = match after_nights && rules.min_rest_after_nights_hours > 0.0 {
+            true => (rules.min_rest_after_nights_hours, "after nights"),
+            false => (rules.min_rest_hours, "between duties"),
+        };
+        if minimum > 0.0 && rest < minimum {
+            violations.push(Violation {
+                first_date: previous.0.date(),
+                last_date: next.0.date(),
+                message: format!("only {} hours rest {description} (minimum {})", format_hours(rest), format_hours(minimum)),
+            });
+        }
+    }
+
+    // Runs of duties on consecutive days
+    if rules.max_consecutive_duties > 0 {
+        for run in consecutive_runs(&duties) {
+            if run.len() > rules.max_consecutive_duties as usize {
+                violations.push(Violation {
+                    first_date: run[0].0.date(),
+                    last_date: run[run.len() - 1].0.date(),
+                    message: format!("{} consecutive duties (maximum {})", run.len(), rules.max_consecutive_duties),
+                });
+            }
+        }
+    }
+    if rules.max_consecutive_nights > 0 {
+        let nights: Vec<_> = duties.iter().copied().filter(|duty| is_night(*duty)).collect();
+        for run in consecutive_runs(&nights) {
+            if run.len() > rules.max_consecutive_nights as usize {
+                violations.push(Violation {
+                    first_date: run[0].0.date(),
+                    last_date: run[run.len() - 1].0.date(),
+                    message: format!("{} consecutive nights (maximum {})", run.len(), rules.max_consecutive_nights),
+                });
+            }
+        }
+    }
+
+    // Hours in any rolling period, starting from the start of each duty. A window is only reported if it
+    // reaches a duty that an earlier reported window didn't, to avoid listing the same busy spell repeatedly.
+    if rules.max_hours_in_period > 0.0 && rules.period_days > 0 {
+        let period = TimeDelta::days(rules.period_days as i64);
+        let mut last_reported = None;
+        for (i, (window_start, _)) in duties.iter().enumerate() {
+            let window_end = *window_start + period;
+            let mut total = 0.0;
+            let mut last_in_window = i;
+            for (j, (start, end)) in duties.iter().enumerate().skip(i) {
+                if *start >= window_end {
+                    break;
+                }
+                total += hours((*end).min(window_end) - *start);
+                last_in_window = j;
+            }
+            if total > rules.max_hours_in_period && last_reported.is_none_or(|last| last_in_window > last) {
+                last_reported = Some(last_in_window);
+                violations.push(Violation {
+                    first_date: window_start.date(),
+                    last_date: duties[last_in_window].0.date(),
+                    message: format!("{} hours worked in {} days (maximum {})", format_hours(total), rules.period_days, format_hours(rules.max_hours_in_period)),
+                });
+            }
+        }
+    }
+
+    violations.sort_by_key(|violation| (violation.first_date, violation.last_date));
+    violations
+}
+
+/// Night duties finish on the day after they start
+fn is_night(duty: (NaiveDateTime, NaiveDateTime)) -> bool {
+    duty.1.date() > duty.0.date()
+}
+
+fn is_next_day(previous: (NaiveDateTime, NaiveDateTime), next: (NaiveDateTime, NaiveDateTime)) -> bool {
+    previous.0.date().checked_add_days(Days::new(1)) == Some(next.0.date())
+}
+
+/// Split duties into runs that start on consecutive days
+fn consecutive_runs(duties: &[(NaiveDateTime, NaiveDateTime)]) -> Vec<&[(NaiveDateTime, NaiveDateTime)]> {
+    let mut runs = Vec::new();
+    let mut run_start = 0;
+    for i in 1..=duties.len() {
+        if i == duties.len() || !is_next_day(duties[i - 1], duties[i]) {
+            runs.push(&duties[run_start..i]);
+            run_start = i;
+        }
+    }
+    runs
+}
+
+fn hours(delta: TimeDelta) -> f64 {
+    delta.num_minutes() as f64 / 60.0
+}
+
+fn format_hours(hours: f64) -> String {
+    let formatted = format!("{hours:.2}");
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roster::tests::events;
+
+    /// The violations in codes from 1 January 2025, as "day-day: message"
+    fn violations(rules: &RuleConfig, codes: &[&str]) -> Vec<String> {
+        check_events(rules, &events(codes)).into_iter()
+            .map(|violation| format!("{}-{}: {}", violation.first_date.format("%-d"), violation.last_date.format("%-d"), violation.message))
+            .collect()
+    }
+
+    #[test]
+    fn rest_between_duties() {
+        let rules = RuleConfig::default();
+        assert_eq!(violations(&rules, &["D4", "M"]), ["1-2: only 8 hours rest between duties (minimum 11)"]);
+        assert!(violations(&rules, &["M", "D4", "//", "D4", "D3"]).is_empty());
+    }
+
+    #[test]
+    fn rest_after_nights() {
+        let rules = RuleConfig::default();
+        assert_eq!(violations(&rules, &["N", "N", "//", "D4"]), ["2-4: only 32.5 hours rest after nights (minimum 48)"]);
+        assert!(violations(&rules, &["N", "N", "//", "//", "D4"]).is_empty());
+
+        let rules = RuleConfig { min_rest_after_nights_hours: 0.0, ..RuleConfig::default() };
+        assert!(violations(&rules, &["N", "N", "//", "D4"]).is_empty());
+    }
+
+    #[test]
+    fn consecutive_duties_and_nights() {
+        let rules = RuleConfig::default();
+        assert_eq!(violations(&rules, &["M", "M", "M", "M", "M", "M", "M"]), ["1-7: 7 consecutive duties (maximum 6)"]);
+        assert!(violations(&rules, &["M", "M", "M", "//", "M", "M", "M"]).is_empty());
+        assert_eq!(violations(&rules, &["N", "N", "N", "N", "N"]), ["1-5: 5 consecutive nights (maximum 4)"]);
+    }
+
+    #[test]
+    fn hours_in_a_period() {
+        let rules = RuleConfig { max_hours_in_period: 40.0, ..RuleConfig::default() };
+        assert!(violations(&rules, &["D2", "D2", "D2", "D2"]).is_empty());
+        // The windows starting on the 1st and 2nd both go over, but the second adds no new duties
+        assert_eq!(violations(&rules, &["D2", "D2", "D2", "D2", "D2"]), ["1-5: 42.5 hours worked in 7 days (maximum 40)"]);
+        assert_eq!(
+            violations(&rules, &["D2", "D2", "D2", "D2", "D2", "//", "//", "D2"]),
+            ["1-5: 42.5 hours worked in 7 days (maximum 40)", "2-8: 42.5 hours worked in 7 days (maximum 40)"],
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Notes that roster managers attach to cells, e.g. "Swap with JB". calamine doesn't read them, so they're
+//! taken straight from the comments part of the workbook
+
+use std::{collections::HashMap, path::Path};
+
+use chrono::{Days, NaiveDate};
+
+use crate::{package::{self, attribute, elements, unescape}, roster::CalendarEvent};
+
+/// Threaded comments are also saved as notes for older versions of Excel, with this before the text
+const THREADED_COMMENT_MARKER: &str = "\nComment:\n";
+
+
+/// The comments on a sheet, by A1-style cell address. Sheets without comments give nothing
+pub fn read(path: &Path, sheet: &str) -> anyhow::Result<HashMap<String, String>> {
+    let mut archive = package::open(path)?;
+    let Some(sheet_path) = package::sheet_path(&mut archive, sheet)? else {
+        return Ok(HashMap::new());
+    };
+    let Some(comments_path) = package::related_part(&mut archive, &sheet_path, "/comments")? else {
+        return Ok(HashMap::new());
+    };
+    let Some(xml) = package::read_part(&mut archive, &comments_path)? else {
+        return Ok(HashMap::new());
+    };
+    Ok(parse(&xml))
+}
+
+/// Read each comment's text from the comments part's XML, without the author's name
+fn parse(xml: &str) -> HashMap<String, String> {
+    let authors: Vec<String> = elements(xml, "author").map(|(_, author)| unescape(author)).collect();
+    let mut comments = HashMap::new();
+    for (tag, body) in elements(xml, "comment") {
+        let Some(address) = attribute(tag, "ref") else {
+            continue;
+        };
+        let mut text: String = elements(body, "t").map(|(_, text)| unescape(text)).collect();
+        // Notes start with the author's name in bold
+        let author = attribute(tag, "authorId").and_then(|id| id.parse::<usize>().ok()).and_then(|id| authors.get(id));
+        if let Some(rest) = author.and_then(|author| text.strip_prefix(&format!("{author}:"))) {
+            text = rest.to_string();
+        }
+        if let Some((_, rest)) = text.split_once(THREADED_COMMENT_MARKER) {
+            text = rest.to_string();
+        }
+        let text = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n");
+        if !text.is_empty() {
+            comments.insert(address.to_uppercase(), text);
+        }
+    }
+    comments
+}
+
+/// Add each day's note to the description of the event on that day. Notes on days without an event, e.g.
+/// days off, get an all-day event of their own
+pub fn annotate_events(first_day_of_month: NaiveDate, notes: &[Option<&str>], events: &mut Vec<CalendarEvent>) {
+    for (i, note) in notes.iter().enumerate() {
+        let Some(note) = note else {
+            continue;
+        };
+        let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+        match events.iter_mut().find(|event| event.is_on(date)) {
+            Some(event @ CalendarEvent::MultiDay { .. }) => event.append_description(&format!("{}: {note}", date.format("%-d %B"))),
+            Some(event) => event.append_description(note),
+            None => events.push(CalendarEvent::AllDay { name: String::from("Note"), date, description: Some(note.to_string()), categories: Vec::new() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_by_cell() {
+        let xml = r#"<comments><authors><author>Jo Bloggs</author><author>tc={0A1B}</author></authors><commentList>
+            <comment ref="d4" authorId="0"><text><r><rPr><b/></rPr><t>Jo Bloggs:</t></r><r><t xml:space="preserve">&#10;Swap with JB &amp; AK</t></r></text></comment>
+            <comment ref="E5" authorId="1"><text><t>[Threaded comment]&#10;Your version of Excel allows you to read this threaded comment; however, any edits to it will get removed if the file is opened in a newer version of Excel.&#10;Comment:&#10;    Covering for Sam</t></text></comment>
+            <comment ref="F6" authorId="0"><text><t>Jo Bloggs:</t></text></comment>
+        </commentList></comments>"#;
+        let comments = parse(xml);
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments["D4"], "Swap with JB & AK");
+        assert_eq!(comments["E5"], "Covering for Sam");
+    }
+}
@@ -0,0 +1,96 @@
+//! Days that several people are all off
+
+use anyhow::{bail, Context};
+use chrono::{Days, NaiveDate, NaiveTime, TimeDelta};
+
+use crate::{args::CommonArgs, ics, roster::{self, CalendarEvent}, util::{self, ReminderAdvance}, xlsx};
+
+const EVENT_NAME: &str = "Shared day off";
+
+
+pub fn run(args: &CommonArgs) -> anyhow::Result<()> {
+    if args.names.len() < 2 {
+        bail!("Give at least two names to compare");
+    }
+    let roster = xlsx::open_roster(&args.roster)?;
+    let first_day_of_month = roster.first_day_of_month;
+
+    let mut people = Vec::with_capacity(args.names.len());
+    let mut events = Vec::with_capacity(args.names.len());
+    for name in &args.names {
+        let (row, name) = roster.find_name(name)?;
+        let days = roster.events_for_row(row)?;
+        events.push(roster::generate_calendar_events(first_day_of_month, &days));
+        people.push(name);
+    }
+
+    let num_days = util::num_days_in_month(&first_day_of_month);
+    let dates: Vec<NaiveDate> = (0..num_days)
+        .map(|i| first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap())
+        .filter(|date| events.iter().all(|person_events| is_free(person_events, *date)))
+        .collect();
+
+    match &args.output {
+        Some(path) => {
+            let (mut calendar, settings) = ics::new_calendar(None, None, ReminderAdvance::MinutesBefore(60));
+            let description = people.join(", ");
+            let day_off_events: Vec<CalendarEvent> = dates.iter()
+                .map(|date| CalendarEvent::AllDay { name: EVENT_NAME.to_string(), date: *date, description: Some(description.clone()), categories: Vec::new() })
+                .collect();
+            for event in &day_off_events {
+                calendar.add_event(ics::new_event(&settings, event));
+            }
+            calendar.save_file(path).context("Unable to save file")?;
+            println!("Wrote {} shared day(s) off to {}", dates.len(), path.display());
+        },
+        None => {
+            println!("Days off shared by {} in {}", people.join(", "), first_day_of_month.format("%B %Y"));
+            if dates.is_empty() {
+                println!("  None");
+            }
+            for date in dates {
+                println!("  {}", date.format("%a %d %B"));
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Whether none of the events are timed ones that start or run into `date`. A night shift finishing in the
+/// morning means the day isn't free. Codes whose times are unknown are treated as work
+fn is_free(events: &[CalendarEvent], date: NaiveDate) -> bool {
+    let day_start = date.and_time(NaiveTime::MIN);
+    let day_end = day_start + TimeDelta::days(1);
+    !events.iter().any(|event| match event {
+        CalendarEvent::Normal { start, end, .. } => start.date() == date || (*start < day_end && *end > day_start),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roster::tests::events;
+
+    /// The days of January 2025 that are free, given codes from the 1st
+    fn free_days(codes: &[&str]) -> Vec<u32> {
+        let events = events(codes);
+        (1..=codes.len() as u32 + 1).filter(|day| is_free(&events, NaiveDate::from_ymd_opt(2025, 1, *day).unwrap())).collect()
+    }
+
+    #[test]
+    fn rest_days_and_leave_are_free() {
+        assert_eq!(free_days(&["M", "//", "s", "M"]), [2, 3, 5]);
+        assert_eq!(free_days(&["AL", "AL", "M"]), [1, 2, 4]);
+    }
+
+    #[test]
+    fn nights_take_the_next_morning() {
+        assert_eq!(free_days(&["N", "//", "//"]), [3, 4]);
+    }
+
+    #[test]
+    fn unknown_codes_are_work() {
+        assert_eq!(free_days(&["/", "//"]), [2, 3]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Optional settings read from a TOML file. If no file is given with --config, roster-to-ics.toml in the
+//! current directory is used if it exists. Every setting has a default, so the file only needs to contain
+//! the settings being changed, e.g.
+//!
+//! ```toml
+//! [rules]
+//! min_rest_hours = 12
+//! max_consecutive_nights = 4
+//! ```
+
+
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use anyhow::{bail, Context};
+use chrono::{Datelike, NaiveDate, NaiveTime, Weekday};
+use serde::Deserialize;
+
+use crate::holidays::Region;
+
+const DEFAULT_CONFIG_FILE: &str = "roster-to-ics.toml";
+
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rules: RuleConfig,
+    pub allowance: AllowanceConfig,
+    pub holidays: HolidayConfig,
+    pub leave: LeaveConfig,
+    pub coverage: CoverageConfig,
+    pub carpool: CarpoolConfig,
+    /// What cell fill colours mean, by colour
+    pub colours: HashMap<String, ColourMeaning>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HolidayConfig {
+    /// Which bundled bank holidays to use: "england-and-wales", "scotland", "northern-ireland" or "none"
+    pub region: Region,
+    /// Files of extra holidays to load. See the holidays module for the format
+    pub files: Vec<PathBuf>,
+}
+impl Default for HolidayConfig {
+    fn default() -> Self {
+        Self { region: Region::EnglandAndWales, files: Vec::new() }
+    }
+}
+
+/// Working-time limits used by the check subcommand. Setting a limit to 0 disables that rule.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Minimum hours between the end of one duty and the start of the next
+    pub min_rest_hours: f64,
+    /// Maximum number of duties on consecutive days
+    pub max_consecutive_duties: u32,
+    /// Maximum number of night duties on consecutive days
+    pub max_consecutive_nights: u32,
+    /// Minimum hours between the end of a run of nights and the start of the next duty
+    pub min_rest_after_nights_hours: f64,
+    /// Maximum hours worked in any period of `period_days` days
+    pub max_hours_in_period: f64,
+    pub period_days: u32,
+}
+impl Default for RuleConfig {
+    fn default() -> Self {
+        Self {
+            min_rest_hours: 11.0,
+            max_consecutive_duties: 6,
+            max_consecutive_nights: 4,
+            min_rest_after_nights_hours: 48.0,
+            max_hours_in_period: 60.0,
+            period_days: 7,
+        }
+    }
+}
+
+/// Unsocial-hours enhancements used by the allowance subcommand. Rates are per hour, and each hour falls
+/// into exactly one bucket: bank holiday, then Sunday, then Saturday, then weekday night or weekday day.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AllowanceConfig {
+    /// When weekday night hours start and end, as "HH:MM"
+    pub night_start: NaiveTime,
+    pub night_end: NaiveTime,
+    pub weekday_day_rate: f64,
+    pub weekday_night_rate: f64,
+    pub saturday_rate: f64,
+    pub sunday_rate: f64,
+    pub bank_holiday_rate: f64,
+}
+impl Default for AllowanceConfig {
+    fn default() -> Self {
+        Self {
+            night_start: NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+            night_end: NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+            weekday_day_rate: 0.0,
+            weekday_night_rate: 0.0,
+            saturday_rate: 0.0,
+            sunday_rate: 0.0,
+            bank_holiday_rate: 0.0,
+        }
+    }
+}
+
+/// Settings for the leave ledger
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeaveConfig {
+    /// Where the ledger is kept
+    pub ledger: PathBuf,
+    /// Record the selected person's leave in the ledger every time a calendar is generated
+    pub record_on_generate: bool,
+    /// Days of annual leave per leave year
+    pub allowance_days: f64,
+    /// Allowances for particular people, by name as it appears on the roster
+    pub person_allowance_days: HashMap<String, f64>,
+    /// The first day of the leave year, as "MM-DD". A start of 02-29 is taken as 02-28 in other years
+    pub leave_year_start: String,
+}
+impl Default for LeaveConfig {
+    fn default() -> Self {
+        Self {
+            ledger: PathBuf::from("leave-ledger.json"),
+            record_on_generate: false,
+            allowance_days: 25.0,
+            person_allowance_days: HashMap::new(),
+            leave_year_start: String::from("01-01"),
+        }
+    }
+}
+
+impl LeaveConfig {
+    /// The month and day the leave year starts on
+    pub fn leave_year_start(&self) -> anyhow::Result<(u32, u32)> {
+        // 2000 is a leap year, so 02-29 is allowed
+        match NaiveDate::parse_from_str(&format!("2000-{}", self.leave_year_start.trim()), "%Y-%m-%d") {
+            Ok(date) => Ok((date.month(), date.day())),
+            Err(_) => bail!("Invalid leave_year_start '{}'. It must be in the form MM-DD", self.leave_year_start),
+        }
+    }
+}
+
+/// Minimum staffing used by the coverage subcommand, by shift code. A minimum is either a number for
+/// every day or a table with a default and overrides for particular days, e.g.
+///
+/// ```toml
+/// [coverage.minimums]
+/// N = 2
+/// M = { default = 2, sat = 1, sun = 1 }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoverageConfig {
+    pub minimums: HashMap<String, ShiftMinimum>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ShiftMinimum {
+    Every(u32),
+    ByDay(DayMinimums),
+}
+impl ShiftMinimum {
+    pub fn on(&self, weekday: Weekday) -> u32 {
+        match self {
+            ShiftMinimum::Every(minimum) => *minimum,
+            ShiftMinimum::ByDay(days) => {
+                let day = match weekday {
+                    Weekday::Mon => days.mon,
+                    Weekday::Tue => days.tue,
+                    Weekday::Wed => days.wed,
+                    Weekday::Thu => days.thu,
+                    Weekday::Fri => days.fri,
+                    Weekday::Sat => days.sat,
+                    Weekday::Sun => days.sun,
+                };
+                day.unwrap_or(days.default)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DayMinimums {
+    pub default: u32,
+    pub mon: Option<u32>,
+    pub tue: Option<u32>,
+    pub wed: Option<u32>,
+    pub thu: Option<u32>,
+    pub fri: Option<u32>,
+    pub sat: Option<u32>,
+    pub sun: Option<u32>,
+}
+
+/// Settings for finding people to travel in with
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CarpoolConfig {
+    /// How far apart two people's start and end times can be, in minutes, and still travel together
+    pub tolerance_minutes: u32,
+    /// Where people live, by name as it appears on the roster. Colleagues in the same area are suggested first
+    pub home_areas: HashMap<String, String>,
+}
+
+/// What a cell fill colour means. Colours are given as hex RGB, e.g.
+///
+/// ```toml
+/// [colours.FFFF00]
+/// category = "Overtime"
+///
+/// [colours.FF0000]
+/// marker = "changed"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColourMeaning {
+    /// Added to the categories of events on days filled with the colour
+    pub category: Option<String>,
+    /// Added in brackets to the names of events on days filled with the colour
+    pub marker: Option<String>,
+}
+
+/// Load the config from `path`, or from the default file if it exists
+pub fn load(path: Option<&Path>) -> anyhow::Result<Config> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+            if !default.exists() {
+                return Ok(Config::default());
+            }
+            default
+        },
+    };
+    let contents = std::fs::read_to_string(&path).with_context(|| format!("Unable to read config file {}", path.display()))?;
+    let config: Config = toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))?;
+    config.leave.leave_year_start().with_context(|| format!("Invalid config file {}", path.display()))?;
+    Ok(config)
+}
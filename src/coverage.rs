@@ -0,0 +1,250 @@
+//! How many people are on each shift each day, compared with the minimum staffing in the config file
+
+use std::{collections::BTreeMap, fs::File, io::{BufWriter, IsTerminal, Write}, path::Path};
+
+use anyhow::Context;
+use chrono::{Datelike, Days, NaiveDate};
+
+use crate::{args::{CoverageArgs, CoverageFormat}, config::{self, CoverageConfig}, csv, html, roster::{self, EventType}, xlsx};
+
+const UNDERSTAFFED: &str = "\x1b[97;41m";
+const RESET: &str = "\x1b[0m";
+const HTML_STYLE: &str = r#"
+body { font-family: Arial, Helvetica, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #999; padding: 0.3em; text-align: center; min-width: 1.8em; }
+th { background: #444; color: #fff; font-weight: normal; }
+td.under { background: #e57373; font-weight: bold; }
+"#;
+
+
+/// The number of people on one shift code on each day of the month, and the minimum for each day
+struct ShiftCoverage {
+    code: String,
+    counts: Vec<u32>,
+    minimums: Vec<u32>,
+}
+impl ShiftCoverage {
+    fn is_understaffed(&self, day: usize) -> bool {
+        self.counts[day] < self.minimums[day]
+    }
+}
+
+pub fn run(args: &CoverageArgs) -> anyhow::Result<()> {
+    let config = config::load(args.config.as_deref())?;
+    let roster = xlsx::open_roster(&args.roster)?;
+    let people = roster.people(args.watch.as_deref())?;
+
+    let mut days_by_person = Vec::with_capacity(people.len());
+    for (row, _) in &people {
+        days_by_person.push(roster.events_for_row(*row)?);
+    }
+    let first_day_of_month = roster.first_day_of_month;
+    let coverage = count_shifts(&config.coverage, first_day_of_month, &days_by_person);
+
+    match args.format {
+        CoverageFormat::Table => {
+            let colour = !args.no_colour && args.output.is_none() && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+            let table = table(first_day_of_month, &coverage, colour);
+            match &args.output {
+                Some(path) => write_file(path, &table)?,
+                None => print!("{table}"),
+            }
+        },
+        CoverageFormat::Csv => {
+            let mut contents = Vec::new();
+            write_csv(&mut contents, first_day_of_month, &coverage)?;
+            match &args.output {
+                Some(path) => write_file(path, &String::from_utf8(contents)?)?,
+                None => print!("{}", String::from_utf8(contents)?),
+            }
+        },
+        // Clap requires an output path for HTML
+        CoverageFormat::Html => write_file(args.output.as_ref().unwrap(), &to_html(first_day_of_month, &coverage))?,
+    }
+    Ok(())
+}
+
+/// Count the shifts worked each day. Every shift code that anyone works, or that has a minimum, gets a row,
+/// in order of start time. Leave and days off aren't counted
+fn count_shifts(config: &CoverageConfig, first_day_of_month: NaiveDate, days_by_person: &[Vec<EventType>]) -> Vec<ShiftCoverage> {
+    let summer = roster::is_summer(&first_day_of_month);
+    let num_days = days_by_person.first().map(|days| days.len()).unwrap_or(0);
+
+    let mut counts: BTreeMap<RowKey, Vec<u32>> = BTreeMap::new();
+    for code in config.minimums.keys() {
+        if let Some(key) = row_key(&EventType::from(code.as_str()), summer) {
+            counts.entry(key).or_insert_with(|| vec![0; num_days]);
+        }
+    }
+    for days in days_by_person {
+        for (i, day) in days.iter().enumerate() {
+            if let Some(key) = row_key(day, summer) {
+                counts.entry(key).or_insert_with(|| vec![0; num_days])[i] += 1;
+            }
+        }
+    }
+
+    counts.into_iter().map(|((_, _, code), counts)| {
+        let minimum = config.minimums.iter().find(|(key, _)| EventType::from(key.as_str()).code() == code).map(|(_, minimum)| minimum);
+        let minimums = (0..num_days).map(|i| {
+            let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+            minimum.map(|minimum| minimum.on(date.weekday())).unwrap_or(0)
+        }).collect();
+        ShiftCoverage { code, counts, minimums }
+    }).collect()
+}
+
+/// Whether the times are unknown, then the start time, then the code, so that rows come out in order
+type RowKey = (bool, (u32, u32), String);
+
+/// None for leave and days off. Overtime counts towards the shift it's marked on
+fn row_key(event_type: &EventType, summer: bool) -> Option<RowKey> {
+    let event_type = event_type.base();
+    let (hour_start, minute_start, _, _) = event_type.start_and_end_time(summer)?;
+    Some((matches!(event_type, EventType::Other { .. }), (hour_start, minute_start), event_type.code()))
+}
+
+fn table(first_day_of_month: NaiveDate, coverage: &[ShiftCoverage], colour: bool) -> String {
+    let num_days = coverage.first().map(|shift| shift.counts.len()).unwrap_or(0);
+    let dates: Vec<NaiveDate> = (0..num_days).map(|i| first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap()).collect();
+    let code_width = coverage.iter().map(|shift| shift.code.len()).max().unwrap_or(0).max(5);
+
+    let mut table = format!("Coverage for {}\n", first_day_of_month.format("%B %Y"));
+    table.push_str(&format!("{:<code_width$}", "Shift"));
+    for date in &dates {
+        table.push_str(&format!(" {:>3}", date.day()));
+    }
+    table.push_str(&format!("\n{:<code_width$}", ""));
+    for date in &dates {
+        table.push_str(&format!(" {:>3}", &date.weekday().to_string()[..2]));
+    }
+    table.push('\n');
+
+    let mut understaffed = Vec::new();
+    for shift in coverage {
+        table.push_str(&format!("{:<code_width$}", shift.code));
+        for (i, date) in dates.iter().enumerate() {
+            if shift.is_understaffed(i) {
+                let cell = format!("{:>3}", format!("{}!", shift.counts[i]));
+                table.push_str(&if colour { format!(" {UNDERSTAFFED}{cell}{RESET}") } else { format!(" {cell}") });
+                understaffed.push(format!("{} {}: {} of {}", date.format("%a %d"), shift.code, shift.counts[i], shift.minimums[i]));
+            } else {
+                table.push_str(&format!(" {:>3}", shift.counts[i]));
+            }
+        }
+        table.push('\n');
+    }
+
+    if understaffed.is_empty() {
+        table.push_str("\nNo shifts below minimum staffing\n");
+    } else {
+        table.push_str(&format!("\n{} shift(s) below minimum staffing:\n", understaffed.len()));
+        for line in understaffed {
+            table.push_str(&format!("  {line}\n"));
+        }
+    }
+    table
+}
+
+/// One row per shift and one column per date. Understaffed cells read e.g. "1 (min 2)"
+fn write_csv(writer: &mut impl Write, first_day_of_month: NaiveDate, coverage: &[ShiftCoverage]) -> anyhow::Result<()> {
+    let num_days = coverage.first().map(|shift| shift.counts.len()).unwrap_or(0);
+    let dates = (0..num_days).map(|i| first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap().format("%Y-%m-%d").to_string());
+    csv::write_record(writer, std::iter::once(String::from("Shift")).chain(dates))?;
+    for shift in coverage {
+        let cells = (0..num_days).map(|i| match shift.is_understaffed(i) {
+            true => format!("{} (min {})", shift.counts[i], shift.minimums[i]),
+            false => shift.counts[i].to_string(),
+        });
+        csv::write_record(writer, std::iter::once(shift.code.clone()).chain(cells))?;
+    }
+    Ok(())
+}
+
+fn to_html(first_day_of_month: NaiveDate, coverage: &[ShiftCoverage]) -> String {
+    let num_days = coverage.first().map(|shift| shift.counts.len()).unwrap_or(0);
+    let title = format!("Coverage - {}", first_day_of_month.format("%B %Y"));
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n", html::escape(&title)));
+    html.push_str(&format!("<h1>{}</h1>\n<table>\n<tr><th>Shift</th>", html::escape(&title)));
+    for i in 0..num_days {
+        let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+        html.push_str(&format!("<th>{}<br>{}</th>", &date.weekday().to_string()[..2], date.day()));
+    }
+    html.push_str("</tr>\n");
+    for shift in coverage {
+        html.push_str(&format!("<tr><th>{}</th>", html::escape(&shift.code)));
+        for i in 0..num_days {
+            if shift.is_understaffed(i) {
+                html.push_str(&format!("<td class=\"under\" title=\"Minimum {}\">{}</td>", shift.minimums[i], shift.counts[i]));
+            } else {
+                html.push_str(&format!("<td>{}</td>", shift.counts[i]));
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn write_file(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let file = File::create(path).context("Unable to create file")?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(contents.as_bytes()).context("Unable to write file")?;
+    writer.flush().context("Unable to write file")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::{DayMinimums, ShiftMinimum}, roster::tests::{days, january_2025}};
+
+    const PEOPLE: &[[&str; 5]] = &[
+        ["M", "M", "AL", "N", "N"],
+        ["M", "OT M", "//", "", "N"],
+        ["D4", "N", "M", "", "TRN"],
+    ];
+
+    /// Wednesday 1 to Sunday 5 January 2025
+    fn coverage(people: &[[&str; 5]]) -> Vec<ShiftCoverage> {
+        let config = CoverageConfig {
+            minimums: [
+                ("M".to_string(), ShiftMinimum::Every(2)),
+                ("d1".to_string(), ShiftMinimum::Every(1)),
+                ("N".to_string(), ShiftMinimum::ByDay(DayMinimums { default: 1, sat: Some(2), ..DayMinimums::default() })),
+            ].into_iter().collect(),
+        };
+        let days_by_person: Vec<Vec<EventType>> = people.iter().map(|codes| days(codes)).collect();
+        count_shifts(&config, january_2025(), &days_by_person)
+    }
+
+    #[test]
+    fn shifts_are_counted_in_order_of_start_time() {
+        let coverage = coverage(PEOPLE);
+        let codes: Vec<&str> = coverage.iter().map(|shift| shift.code.as_str()).collect();
+        // D1 has a minimum but nobody on it, and codes with unknown times come last
+        assert_eq!(codes, ["M", "D1", "D4", "N", "TRN"]);
+        assert_eq!(coverage[0].counts, [2, 2, 1, 0, 0]);
+        assert_eq!(coverage[1].counts, [0, 0, 0, 0, 0]);
+        assert_eq!(coverage[2].counts, [1, 0, 0, 0, 0]);
+        assert_eq!(coverage[3].counts, [0, 1, 0, 1, 2]);
+        assert_eq!(coverage[4].counts, [0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn understaffed_days() {
+        let coverage = coverage(PEOPLE);
+        let understaffed = |shift: &ShiftCoverage| (0..5).filter(|day| shift.is_understaffed(*day)).collect::<Vec<_>>();
+        assert_eq!(understaffed(&coverage[0]), [2, 3, 4]);
+        assert_eq!(understaffed(&coverage[1]), [0, 1, 2, 3, 4]);
+        assert!(understaffed(&coverage[2]).is_empty());
+        // Saturday needs two nights
+        assert_eq!(coverage[3].minimums, [1, 1, 1, 2, 1]);
+        assert_eq!(understaffed(&coverage[3]), [0, 2, 3]);
+        assert!(understaffed(&coverage[4]).is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use std::{fs::File, io::{BufWriter, Write}, path::Path};
+
+use anyhow::Context;
+use chrono::{Days, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::{roster::CalendarEvent, util::ReminderAdvance};
+
+// Google Calendar expects US-style dates and 12 hour times
+const GOOGLE_HEADER: &[&str] = &["Subject", "Start Date", "Start Time", "End Date", "End Time", "All Day Event", "Description", "Location", "Private"];
+const GOOGLE_DATE_FORMAT_STRING: &str = "%m/%d/%Y";
+const GOOGLE_TIME_FORMAT_STRING: &str = "%I:%M %p";
+
+// The column layout produced by Outlook's own CSV export. Dates are in the UK locale
+const OUTLOOK_HEADER: &[&str] = &[
+    "Subject", "Start Date", "Start Time", "End Date", "End Time", "All day event", "Reminder on/off", "Reminder Date", "Reminder Time",
+    "Meeting Organizer", "Required Attendees", "Optional Attendees", "Meeting Resources", "Billing Information", "Categories",
+    "Description", "Location", "Mileage", "Priority", "Private", "Sensitivity", "Show time as",
+];
+const OUTLOOK_DATE_FORMAT_STRING: &str = "%d/%m/%Y";
+const OUTLOOK_TIME_FORMAT_STRING: &str = "%H:%M:%S";
+const OUTLOOK_PRIORITY_NORMAL: &str = "Normal";
+const OUTLOOK_SENSITIVITY_NORMAL: &str = "Normal";
+const OUTLOOK_SHOW_TIME_AS_BUSY: &str = "2";
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum CsvLayout {
+    Google,
+    Outlook,
+}
+
+/// Write the events as a CSV file for importing into Google Calendar or Outlook. If `reminder` is given,
+/// Outlook reminders are switched on. Google's CSV import has no reminder columns.
+pub fn save_file(path: impl AsRef<Path>, layout: CsvLayout, reminder: Option<&ReminderAdvance>, events: &[CalendarEvent]) -> anyhow::Result<()> {
+    let file = File::create(path).context("Unable to create file")?;
+    let mut writer = BufWriter::new(file);
+
+    let header = match layout {
+        CsvLayout::Google => GOOGLE_HEADER,
+        CsvLayout::Outlook => OUTLOOK_HEADER,
+    };
+    write_record(&mut writer, header.iter().map(|s| s.to_string()))?;
+
+    for event in events {
+        let record = match layout {
+            CsvLayout::Google => google_record(event),
+            CsvLayout::Outlook => outlook_record(event, reminder),
+        };
+        write_record(&mut writer, record)?;
+    }
+
+    writer.flush().context("Unable to write file")?;
+    Ok(())
+}
+
+fn google_record(event: &CalendarEvent) -> Vec<String> {
+    let date = |date: &NaiveDate| date.format(GOOGLE_DATE_FORMAT_STRING).to_string();
+    let time = |date_time: &NaiveDateTime| date_time.format(GOOGLE_TIME_FORMAT_STRING).to_string();
+
+    // Google treats the end date of an all-day event as inclusive
+    let (name, start_date, start_time, end_date, end_time, all_day) = match event {
+        CalendarEvent::Normal { name, start, end, .. } => (name, date(&start.date()), time(start), date(&end.date()), time(end), false),
+        CalendarEvent::AllDay { name, date: day, .. } => (name, date(day), String::new(), date(day), String::new(), true),
+        CalendarEvent::MultiDay { name, start, end, .. } => {
+            let last_day = end.checked_sub_days(Days::new(1)).unwrap();
+            (name, date(start), String::new(), date(&last_day), String::new(), true)
+        },
+    };
+
+    let description = event.description().unwrap_or_default().to_string();
+    vec![name.clone(), start_date, start_time, end_date, end_time, bool_string(all_day).into(), description, String::new(), bool_string(false).into()]
+}
+
+fn outlook_record(event: &CalendarEvent, reminder: Option<&ReminderAdvance>) -> Vec<String> {
+    let date = |date: &NaiveDate| date.format(OUTLOOK_DATE_FORMAT_STRING).to_string();
+    let time = |date_time: &NaiveDateTime| date_time.format(OUTLOOK_TIME_FORMAT_STRING).to_string();
+
+    // Outlook treats the end date of an all-day event as exclusive, like iCalendar
+    let (name, start, end, all_day) = match event {
+        CalendarEvent::Normal { name, start, end, .. } => (name, *start, *end, false),
+        CalendarEvent::AllDay { name, date, .. } => (name, date.and_time(NaiveTime::MIN), date.checked_add_days(Days::new(1)).unwrap().and_time(NaiveTime::MIN), true),
+        CalendarEvent::MultiDay { name, start, end, .. } => (name, start.and_time(NaiveTime::MIN), end.and_time(NaiveTime::MIN), true),
+    };
+
+    let (reminder_date, reminder_time) = match reminder {
+        Some(reminder) => {
+            let reminder_at = start - reminder.duration();
+            (date(&reminder_at.date()), time(&reminder_at))
+        },
+        None => (String::new(), String::new()),
+    };
+
+    vec![
+        name.clone(), date(&start.date()), time(&start), date(&end.date()), time(&end), bool_string(all_day).into(),
+        bool_string(reminder.is_some()).into(), reminder_date, reminder_time,
+        String::new(), String::new(), String::new(), String::new(), String::new(), event.categories().join(";"),
+        event.description().unwrap_or_default().to_string(), String::new(), String::new(), OUTLOOK_PRIORITY_NORMAL.into(), bool_string(false).into(), OUTLOOK_SENSITIVITY_NORMAL.into(), OUTLOOK_SHOW_TIME_AS_BUSY.into(),
+    ]
+}
+
+fn bool_string(value: bool) -> &'static str {
+    if value { "True" } else { "False" }
+}
+
+pub fn write_record(writer: &mut impl Write, fields: impl IntoIterator<Item = String>) -> anyhow::Result<()> {
+    let fields: Vec<String> = fields.into_iter().map(|field| {
+        if field.contains([',', '"', '\r', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    }).collect();
+    // CSV files use CRLF line endings
+    write!(writer, "{}\r\n", fields.join(",")).context("Unable to write file")?;
+    Ok(())
+}
@@ -0,0 +1,480 @@
+//! Working out which month a roster is for. The -m and -y arguments always win. Otherwise the month and
+//! year are taken together from the most reliable place that gives both, from most to least reliable:
+//! dates in the header row, title cells above the header, the workbook's sheet names, the file name, then the
+//! workbook's document properties. If two places that are equally reliable disagree, the next most reliable
+//! place is used, and if nowhere agrees the month has to be given with -m and -y.
+//!
+//! Months can be written in full or abbreviated ("March", "MAR"), as two-digit numbers joined to the year
+//! ("03-2025", "2025_03", "202503") or in Welsh, French, German, Spanish, Italian or Polish. Two-digit years
+//! only count when attached to the month ("Mar25", "03/25"). A source that mentions more than one month or
+//! year is ambiguous, so it isn't used.
+
+use std::{cmp::Reverse, fs::File, io::Read, path::Path};
+
+use anyhow::bail;
+use calamine::{Data, Range};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+
+use crate::args::RosterArgs;
+
+const MONTH_NAMES: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+// Other ways of writing each month, lower case: English abbreviations, then Welsh, French, German, Spanish, Italian and
+// Polish, with and without accents
+const OTHER_MONTH_NAMES: [&[&str]; 12] = [
+    &["jan", "ionawr", "janvier", "januar", "jänner", "enero", "gennaio", "styczeń", "styczen"],
+    &["feb", "chwefror", "février", "fevrier", "februar", "febrero", "febbraio", "luty"],
+    &["mar", "mawrth", "mars", "märz", "maerz", "marz", "marzo", "marzec"],
+    &["apr", "ebrill", "avril", "abril", "aprile", "kwiecień", "kwiecien"],
+    &["mai", "mayo", "maggio", "maj"],
+    &["jun", "mehefin", "juin", "juni", "junio", "giugno", "czerwiec"],
+    &["jul", "gorffennaf", "juillet", "juli", "julio", "luglio", "lipiec"],
+    &["aug", "awst", "août", "aout", "agosto", "sierpień", "sierpien"],
+    &["sep", "sept", "medi", "septembre", "septiembre", "setiembre", "settembre", "wrzesień", "wrzesien"],
+    &["oct", "hydref", "octobre", "oktober", "octubre", "ottobre", "październik", "pazdziernik"],
+    &["nov", "tachwedd", "novembre", "noviembre", "listopad"],
+    &["dec", "rhagfyr", "décembre", "decembre", "dezember", "diciembre", "dicembre", "grudzień", "grudzien"],
+];
+// Characters that can join a month and year that belong together, e.g. "Mar-25" or "2025_03"
+const JOINERS: &[char] = &[' ', '-', '_', '.', '/'];
+// Month names that are also ordinary words, lower case
+const COMMON_WORDS: &[&str] = &["may", "mar", "mars", "sept"];
+// The document properties that might say which month the roster is for
+const PROPERTY_TAGS: &[&str] = &["dc:title", "dc:subject", "dc:description", "cp:keywords"];
+
+
+/// How much a source is trusted, least first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    DocumentProperties,
+    FileName,
+    SheetName,
+    TitleCell,
+    HeaderDates,
+}
+
+/// What one source says about the month and year
+#[derive(Debug)]
+pub struct Candidate {
+    pub confidence: Confidence,
+    /// Where it was found, for messages, e.g. "sheet name 'March 2025'"
+    pub source: String,
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+    /// Why the month or year couldn't be taken from the source, if it mentions more than one
+    pub ambiguity: Option<String>,
+}
+
+/// Find the first day of the roster's month from the arguments, the workbook and the file name, along with how
+/// reliable the source was. There is no source if the arguments give both the month and year
+pub fn first_day_of_month(args: &RosterArgs, sheet_names: &[String], worksheet: &Range<Data>, header_row: usize) -> anyhow::Result<(NaiveDate, Option<Confidence>)> {
+    if let Some(year) = args.year {
+        if !(2000..2100).contains(&year) {
+            bail!("Invalid year ({year}). Must be between 2000 and 2099.");
+        }
+    }
+    let (year, month, confidence) = match (args.year, args.month) {
+        (Some(year), Some(month)) => (year as i32, month as u32, None),
+        (year, month) => {
+            let candidates = find_candidates(&args.xlsx_path, sheet_names, worksheet, header_row);
+            let (year, month, confidence) = choose(&candidates, year.map(|year| year as i32), month.map(|month| month as u32))?;
+            (year, month, Some(confidence))
+        },
+    };
+    match NaiveDate::from_ymd_opt(year, month, 1) {
+        Some(date) => Ok((date, confidence)),
+        None => bail!("Unable to determine month / year"),
+    }
+}
+
+/// Take the year and month from the most reliable candidates that agree, filling in whatever the arguments
+/// don't give. If candidates that are equally reliable disagree, the next most reliable ones are used instead
+fn choose(candidates: &[Candidate], year: Option<i32>, month: Option<u32>) -> anyhow::Result<(i32, u32, Confidence)> {
+    // Whatever the arguments don't give has to come from a single candidate
+    let complete = |candidate: &Candidate| Some((year.or(candidate.year)?, month.or(candidate.month)?));
+    let mut usable: Vec<(&Candidate, (i32, u32))> = candidates.iter().filter_map(|candidate| complete(candidate).map(|date| (candidate, date))).collect();
+    // Most reliable first
+    usable.sort_by_key(|(candidate, _)| Reverse(candidate.confidence));
+
+    let mut disagreement = false;
+    let mut chosen = None;
+    for group in usable.chunk_by(|(a, _), (b, _)| a.confidence == b.confidence) {
+        if group.iter().all(|(_, date)| *date == group[0].1) {
+            chosen = Some(group[0]);
+            break;
+        }
+        disagreement = true;
+    }
+
+    let Some((best, (year, month))) = chosen else {
+        let (missing, flag) = match (year, month) {
+            (Some(_), _) => ("month", "-m argument"),
+            (_, Some(_)) => ("year", "-y argument"),
+            _ => ("month and year", "-m and -y arguments"),
+        };
+        let mut message = match disagreement {
+            false => format!(
+                "Unable to determine the {missing} of the roster - try specifying it manually with the {flag}.\n\
+                Looked in the dates in the header row, the title cells above it, the sheet names, the file name and the document properties",
+            ),
+            true => format!("Unable to tell which {missing} the roster is for - specify it with the {flag}."),
+        };
+        for candidate in candidates {
+            message.push_str(&format!("\n  Found in {}: {}", candidate.source, describe(candidate)));
+        }
+        bail!(message);
+    };
+
+    // Mention anything that disagrees with what was chosen, as it may mean the wrong month has been picked
+    for (candidate, date) in &usable {
+        if *date != (year, month) {
+            eprintln!("Warning: {} suggests a different month to {} {year}", candidate.source, MONTH_NAMES[month as usize - 1]);
+        }
+    }
+    if disagreement {
+        eprintln!("Warning: more reliable sources disagree with each other, so the month was taken from {}", best.source);
+    }
+    Ok((year, month, best.confidence))
+}
+
+fn describe(candidate: &Candidate) -> String {
+    let found = match (candidate.month, candidate.year) {
+        (Some(month), Some(year)) => format!("{} {year}", MONTH_NAMES[month as usize - 1]),
+        (Some(month), None) => format!("{}, but no year", MONTH_NAMES[month as usize - 1]),
+        (None, Some(year)) => format!("{year}, but no month"),
+        (None, None) => String::from("nothing"),
+    };
+    match &candidate.ambiguity {
+        Some(ambiguity) => format!("{found} - it {ambiguity}"),
+        None => found,
+    }
+}
+
+pub fn find_candidates(path: &Path, sheet_names: &[String], worksheet: &Range<Data>, header_row: usize) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+
+    // Dates in the header row are the days themselves, so the first one gives the month
+    if let Some(date) = worksheet.rows().nth(header_row).into_iter().flatten().find_map(cell_date) {
+        candidates.push(Candidate {
+            confidence: Confidence::HeaderDates,
+            source: format!("the date {} in the header row", date.format("%d/%m/%Y")),
+            year: Some(date.year()),
+            month: Some(date.month()),
+            ambiguity: None,
+        });
+    }
+
+    // Title cells that give both the month and year are taken on their own, as other cells may hold other
+    // dates, e.g. "Updated 20 Feb". If none do, the title may be split across cells, e.g. "MARCH" and "2025"
+    let titles: Vec<&str> = worksheet.rows().take(header_row).flatten().filter_map(|cell| match cell {
+        Data::String(text) if !text.trim().is_empty() => Some(text.trim()),
+        _ => None,
+    }).collect();
+    let complete_titles: Vec<&str> = titles.iter().copied().filter(|text| matches!(parse_month_and_year(text), (Some(_), Some(_), None))).collect();
+    if complete_titles.is_empty() {
+        let text = titles.join(" ");
+        push_if_found(&mut candidates, Confidence::TitleCell, format!("the title cells '{text}'"), &text);
+    }
+    for text in complete_titles {
+        push_if_found(&mut candidates, Confidence::TitleCell, format!("the title cell '{text}'"), text);
+    }
+
+    for sheet_name in sheet_names {
+        push_if_found(&mut candidates, Confidence::SheetName, format!("the sheet name '{sheet_name}'"), sheet_name);
+    }
+
+    if let (Some(file_name), Some(stem)) = (path.file_name().and_then(|os_str| os_str.to_str()), path.file_stem().and_then(|os_str| os_str.to_str())) {
+        push_if_found(&mut candidates, Confidence::FileName, format!("the file name '{file_name}'"), stem);
+    }
+
+    for (tag, value) in document_properties(path) {
+        push_if_found(&mut candidates, Confidence::DocumentProperties, format!("the document property {tag} '{value}'"), &value);
+    }
+
+    candidates
+}
+
+fn push_if_found(candidates: &mut Vec<Candidate>, confidence: Confidence, source: String, text: &str) {
+    let (year, month, ambiguity) = parse_month_and_year(text);
+    if year.is_some() || month.is_some() || ambiguity.is_some() {
+        candidates.push(Candidate { confidence, source, year, month, ambiguity });
+    }
+}
+
+/// A run of letters or of digits, and how it's joined to the tokens either side of it
+#[derive(Debug)]
+struct Token {
+    text: String,
+    /// Joined to the next token closely enough to belong with it
+    joined_to_next: bool,
+    /// The character joining it to the next token, if there is one, e.g. '-' in "03-2025"
+    joiner: Option<char>,
+    /// Directly after the previous token with nothing between, e.g. "3" in "v3"
+    glued_to_previous: bool,
+}
+
+fn tokenise(text: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut gap = String::new();
+    let mut current = String::new();
+    let push = |tokens: &mut Vec<Token>, text: String, gap: &str| {
+        if let Some(last) = tokens.last_mut() {
+            last.joined_to_next = gap.chars().count() <= 1 && gap.chars().all(|c| JOINERS.contains(&c));
+            last.joiner = gap.chars().next().filter(|_| last.joined_to_next);
+        }
+        let glued_to_previous = !tokens.is_empty() && gap.is_empty();
+        tokens.push(Token { text, joined_to_next: false, joiner: None, glued_to_previous });
+    };
+    for c in text.chars() {
+        let continues = current.chars().next().is_some_and(|first| first.is_ascii_digit() == c.is_ascii_digit()) && c.is_alphanumeric();
+        if c.is_alphanumeric() && (current.is_empty() || continues) {
+            current.push(c);
+            continue;
+        }
+        if !current.is_empty() {
+            push(&mut tokens, std::mem::take(&mut current), &gap);
+            gap.clear();
+        }
+        if c.is_alphanumeric() {
+            current.push(c);
+        } else {
+            gap.push(c);
+        }
+    }
+    if !current.is_empty() {
+        push(&mut tokens, current, &gap);
+    }
+    tokens
+}
+
+/// Find the month and year in text such as a file name. A month and year written together ("Mar25", "03-2025",
+/// "2025-03", "202503", "March 2025") are preferred over ones found apart. If the text mentions more than one
+/// month or year, that part is left out and the problem is described.
+pub fn parse_month_and_year(text: &str) -> (Option<i32>, Option<u32>, Option<String>) {
+    let tokens = tokenise(text);
+
+    let mut pairs: Vec<(i32, u32)> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let digits = token.text.chars().all(|c| c.is_ascii_digit());
+        if digits && token.text.len() == 6 {
+            let (first, last) = token.text.split_at(4);
+            if let (Some(year), Some(month)) = (year_number(first), month_number(last)) {
+                pairs.push((year, month));
+            }
+            let (first, last) = token.text.split_at(2);
+            if let (Some(month), Some(year)) = (month_number(first), year_number(last)) {
+                pairs.push((year, month));
+            }
+        }
+        let Some(next) = tokens.get(i + 1).filter(|_| token.joined_to_next) else {
+            continue;
+        };
+        // Numbers are only taken as months in a date, e.g. "2025-03" or "03_2025", and not in e.g. "v3 2025"
+        let numeric_date = token.joiner.is_some_and(|joiner| joiner != ' ');
+        // Two-digit years only count when attached to the month, e.g. "Mar25", "Mar-25" or "03/25", and not in
+        // "March 15" or the day and month of "12/03/2025"
+        let attached_short_year = || short_year(&next.text)
+            .filter(|_| numeric_date || next.glued_to_previous)
+            .filter(|_| !in_longer_date(&tokens, i + 1) && !i.checked_sub(1).is_some_and(|previous| in_longer_date(&tokens, previous)));
+        let pair = match (year_number(&token.text), month_number(&token.text), month_name(&token.text)) {
+            (Some(year), _, _) => month_number(&next.text).filter(|_| numeric_date && next.text.len() == 2)
+                .or_else(|| month_name(&next.text))
+                .map(|month| (year, month)),
+            (_, Some(month), _) if numeric_date && token.text.len() == 2 && !token.glued_to_previous => {
+                year_number(&next.text).or_else(attached_short_year).map(|year| (year, month))
+            },
+            (_, _, Some(month)) => year_number(&next.text).or_else(attached_short_year).map(|year| (year, month)),
+            _ => None,
+        };
+        pairs.extend(pair);
+    }
+    // Anything else that looks like a month or year has to agree with the pairs
+    let mut years: Vec<i32> = tokens.iter().filter_map(|token| year_number(&token.text)).chain(pairs.iter().map(|(year, _)| *year)).collect();
+    years.sort();
+    years.dedup();
+    // Month names that are also ordinary words only count next to a year, e.g. "May 2025" but not "Shifts may change"
+    let mut months: Vec<u32> = tokens.iter()
+        .filter(|token| !COMMON_WORDS.contains(&token.text.to_lowercase().as_str()))
+        .filter_map(|token| month_name(&token.text))
+        .chain(pairs.iter().map(|(_, month)| *month))
+        .collect();
+    months.sort();
+    months.dedup();
+
+    let mut ambiguities = Vec::new();
+    if years.len() > 1 {
+        let found: Vec<String> = years.iter().map(|year| year.to_string()).collect();
+        ambiguities.push(format!("more than one year ({})", found.join(", ")));
+    }
+    if months.len() > 1 {
+        let found: Vec<&str> = months.iter().map(|month| MONTH_NAMES[*month as usize - 1]).collect();
+        ambiguities.push(format!("more than one month ({})", found.join(", ")));
+    }
+    let year = if years.len() == 1 { Some(years[0]) } else { None };
+    let month = if months.len() == 1 { Some(months[0]) } else { None };
+    let ambiguity = (!ambiguities.is_empty()).then(|| format!("mentions {}", ambiguities.join(" and ")));
+    (year, month, ambiguity)
+}
+
+/// Whether the token at `i` and the one after it are numbers in a date, e.g. "12/03" in "12/03/2025"
+fn in_longer_date(tokens: &[Token], i: usize) -> bool {
+    let is_number = |token: &Token| token.text.chars().all(|c| c.is_ascii_digit());
+    match (tokens.get(i), tokens.get(i + 1)) {
+        (Some(token), Some(next)) => token.joiner.is_some_and(|joiner| joiner != ' ') && is_number(token) && is_number(next),
+        _ => false,
+    }
+}
+
+/// A four-digit year from 2000 to 2099
+fn year_number(text: &str) -> Option<i32> {
+    if text.len() != 4 {
+        return None;
+    }
+    text.parse().ok().filter(|year| (2000..2100).contains(year))
+}
+
+/// A two-digit year, as in "Mar25" or "03/25"
+fn short_year(text: &str) -> Option<i32> {
+    if text.len() != 2 {
+        return None;
+    }
+    text.parse::<i32>().ok().map(|year| 2000 + year)
+}
+
+/// A one or two-digit month number
+fn month_number(text: &str) -> Option<u32> {
+    if text.is_empty() || text.len() > 2 {
+        return None;
+    }
+    text.parse().ok().filter(|month| (1..=12).contains(month))
+}
+
+/// A whole word that is a month name in any of the supported languages
+fn month_name(text: &str) -> Option<u32> {
+    if text.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let text = text.to_lowercase();
+    let month = MONTH_NAMES.iter().zip(OTHER_MONTH_NAMES)
+        .position(|(name, others)| name.to_lowercase() == text || others.contains(&text.as_str()))?;
+    Some(month as u32 + 1)
+}
+
+fn cell_date(cell: &Data) -> Option<NaiveDateTime> {
+    match cell {
+        Data::DateTime(date_time) => date_time.as_datetime(),
+        Data::DateTimeIso(text) => NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").ok()
+            .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().map(|date| date.and_time(Default::default()))),
+        _ => None,
+    }
+}
+
+/// The text of the document properties in docProps/core.xml. Workbooks without them give nothing
+fn document_properties(path: &Path) -> Vec<(&'static str, String)> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    let Ok(mut archive) = zip::ZipArchive::new(file) else {
+        return Vec::new();
+    };
+    let mut xml = String::new();
+    let Ok(mut core) = archive.by_name("docProps/core.xml") else {
+        return Vec::new();
+    };
+    if core.read_to_string(&mut xml).is_err() {
+        return Vec::new();
+    }
+
+    PROPERTY_TAGS.iter().filter_map(|tag| {
+        let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+        let end = start + xml[start..].find(&format!("</{tag}>"))?;
+        Some((*tag, xml[start..end].to_string()))
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(parse_month_and_year("Roster March 2025"), (Some(2025), Some(3), None));
+        assert_eq!(parse_month_and_year("roster_mar25"), (Some(2025), Some(3), None));
+        assert_eq!(parse_month_and_year("Roster 2025-03"), (Some(2025), Some(3), None));
+        assert_eq!(parse_month_and_year("roster_03_2025"), (Some(2025), Some(3), None));
+        assert_eq!(parse_month_and_year("roster 202503"), (Some(2025), Some(3), None));
+        assert_eq!(parse_month_and_year("Dienstplan März 2025"), (Some(2025), Some(3), None));
+        assert_eq!(parse_month_and_year("Roster Mar-25"), (Some(2025), Some(3), None));
+        assert_eq!(parse_month_and_year("roster 03/25"), (Some(2025), Some(3), None));
+    }
+
+    #[test]
+    fn numbers_after_month_names_that_are_not_years() {
+        assert_eq!(parse_month_and_year("March 15"), (None, Some(3), None));
+        assert_eq!(parse_month_and_year("March 31 update"), (None, Some(3), None));
+        assert_eq!(parse_month_and_year("Mar 31 update"), (None, None, None));
+        assert_eq!(parse_month_and_year("Updated 12/03/2025"), (Some(2025), Some(3), None));
+    }
+
+    #[test]
+    fn numbers_that_are_not_months() {
+        assert_eq!(parse_month_and_year("roster_v3_2025"), (Some(2025), None, None));
+        assert_eq!(parse_month_and_year("roster 3 2025"), (Some(2025), None, None));
+        assert_eq!(parse_month_and_year("roster 2025 12 copies"), (Some(2025), None, None));
+    }
+
+    #[test]
+    fn words_that_contain_or_are_month_names() {
+        assert_eq!(parse_month_and_year("Shifts may change"), (None, None, None));
+        assert_eq!(parse_month_and_year("Mayor's office"), (None, None, None));
+        assert_eq!(parse_month_and_year("Marching orders 2025"), (Some(2025), None, None));
+        assert_eq!(parse_month_and_year("ROSTER MAY 2025 - shifts may change"), (Some(2025), Some(5), None));
+    }
+
+    #[test]
+    fn ambiguous_text() {
+        let (year, month, ambiguity) = parse_month_and_year("Roster March April 2025");
+        assert_eq!((year, month), (Some(2025), None));
+        assert!(ambiguity.is_some_and(|ambiguity| ambiguity.contains("March, April")));
+
+        let (year, month, ambiguity) = parse_month_and_year("March 2024 to 2025");
+        assert_eq!((year, month), (None, Some(3)));
+        assert!(ambiguity.is_some());
+    }
+
+    /// A sheet with the given title cells in its first row and the header row below
+    fn sheet(titles: &[&str]) -> Range<Data> {
+        let mut worksheet = Range::new((0, 0), (1, titles.len() as u32));
+        for (i, title) in titles.iter().enumerate() {
+            worksheet.set_value((0, i as u32), Data::String(title.to_string()));
+        }
+        worksheet.set_value((1, 0), Data::String(String::from("NAME")));
+        worksheet
+    }
+
+    fn detect(file_name: &str, sheet_names: &[&str], titles: &[&str]) -> anyhow::Result<(i32, u32)> {
+        let sheet_names: Vec<String> = sheet_names.iter().map(|name| name.to_string()).collect();
+        let (year, month, _) = choose(&find_candidates(Path::new(file_name), &sheet_names, &sheet(titles), 1), None, None)?;
+        Ok((year, month))
+    }
+
+    #[test]
+    fn last_updated_dates() {
+        // The title cells disagree, so the file name decides
+        assert_eq!(detect("Roster March 2025 b.xlsx", &["Roster"], &["ROSTER MARCH 2025", "Updated 20 Feb 2025"]).unwrap(), (2025, 3));
+        assert!(detect("roster_v3.xlsx", &["Roster"], &["ROSTER MARCH 2025", "Updated 20 Feb 2025"]).is_err());
+        // An update without a year doesn't count against a title that gives both
+        assert_eq!(detect("roster_v3.xlsx", &["Roster"], &["ROSTER MARCH 2025", "Updated 20 Feb"]).unwrap(), (2025, 3));
+    }
+
+    #[test]
+    fn titles_split_across_cells() {
+        assert_eq!(detect("roster_v3.xlsx", &["Roster"], &["MARCH", "2025"]).unwrap(), (2025, 3));
+    }
+
+    #[test]
+    fn sheet_names() {
+        assert_eq!(detect("roster_v3.xlsx", &["Roster", "Mar 2025 changes"], &["ROSTER"]).unwrap(), (2025, 3));
+        assert_eq!(detect("Roster April 2025.xlsx", &["Roster", "Mar 2025 changes"], &[]).unwrap(), (2025, 3));
+    }
+}
@@ -0,0 +1,204 @@
+//! Cell fill colours, which some rosters use as well as codes, e.g. yellow for overtime or red for changes.
+//! calamine doesn't read cell styles, so they're taken straight from the workbook
+
+use std::{collections::{HashMap, HashSet}, path::Path};
+
+use chrono::{Days, NaiveDate};
+
+use crate::{config::ColourMeaning, package::{self, attribute, elements, tags}, roster::CalendarEvent};
+
+
+/// Excel's default palette, used by colours given as an index rather than RGB
+const DEFAULT_PALETTE: [&str; 64] = [
+    "000000", "FFFFFF", "FF0000", "00FF00", "0000FF", "FFFF00", "FF00FF", "00FFFF",
+    "000000", "FFFFFF", "FF0000", "00FF00", "0000FF", "FFFF00", "FF00FF", "00FFFF",
+    "800000", "008000", "000080", "808000", "800080", "008080", "C0C0C0", "808080",
+    "9999FF", "993366", "FFFFCC", "CCFFFF", "660066", "FF8080", "0066CC", "CCCCFF",
+    "000080", "FF00FF", "FFFF00", "00FFFF", "800080", "800000", "008080", "0000FF",
+    "00CCFF", "CCFFFF", "CCFFCC", "FFFF99", "99CCFF", "FF99CC", "CC99FF", "FFCC99",
+    "3366FF", "33CCCC", "99CC00", "FFCC00", "FF9900", "FF6600", "666699", "969696",
+    "003366", "339966", "003300", "333300", "993300", "993366", "333399", "333333",
+];
+
+/// The fill colours of the cells on a sheet
+#[derive(Debug, Default)]
+pub struct Fills {
+    /// Solid fill colours as hex RGB, e.g. "FFFF00", by A1-style cell address
+    pub colours: HashMap<String, String>,
+    /// The cells with a fill that can't be given as RGB, e.g. a theme colour, by A1-style cell address
+    pub skipped: HashSet<String>,
+}
+
+/// What a fill in styles.xml is
+#[derive(Debug, Clone, PartialEq)]
+enum Fill {
+    None,
+    Colour(String),
+    /// A fill that isn't a plain colour, or whose colour comes from the workbook's theme
+    Unknown,
+}
+
+/// The fill colours of the cells on a sheet. Sheets without styles give nothing
+pub fn read(path: &Path, sheet: &str) -> anyhow::Result<Fills> {
+    let mut archive = package::open(path)?;
+    let Some(sheet_path) = package::sheet_path(&mut archive, sheet)? else {
+        return Ok(Fills::default());
+    };
+    let Some(styles_path) = package::related_part(&mut archive, "xl/workbook.xml", "/styles")? else {
+        return Ok(Fills::default());
+    };
+    let (Some(sheet_xml), Some(styles)) = (package::read_part(&mut archive, &sheet_path)?, package::read_part(&mut archive, &styles_path)?) else {
+        return Ok(Fills::default());
+    };
+    Ok(parse(&styles, &sheet_xml))
+}
+
+/// Match each cell in a sheet's XML to its fill in styles.xml
+fn parse(styles: &str, sheet_xml: &str) -> Fills {
+    // A workbook can replace the default palette with its own
+    let palette: Vec<String> = elements(styles, "indexedColors").next()
+        .map(|(_, colours)| tags(colours, "rgbColor").filter_map(|colour| attribute(colour, "rgb")).map(|rgb| normalise(&rgb)).collect())
+        .unwrap_or_else(|| DEFAULT_PALETTE.iter().map(|rgb| rgb.to_string()).collect());
+
+    // Each cell's s attribute is an index into cellXfs, which gives an index into fills
+    let fills: Vec<Fill> = elements(styles, "fills").next()
+        .map(|(_, fills)| elements(fills, "fill").map(|(_, fill)| fill_colour(fill, &palette)).collect())
+        .unwrap_or_default();
+    let cell_fills: Vec<Option<usize>> = elements(styles, "cellXfs").next()
+        .map(|(_, xfs)| tags(xfs, "xf").map(|xf| attribute(xf, "fillId").and_then(|id| id.parse().ok())).collect())
+        .unwrap_or_default();
+
+    let mut result = Fills::default();
+    for cell in tags(sheet_xml, "c") {
+        let fill = attribute(cell, "s")
+            .and_then(|style| style.parse::<usize>().ok())
+            .and_then(|style| *cell_fills.get(style)?)
+            .and_then(|fill| fills.get(fill));
+        match (attribute(cell, "r"), fill) {
+            (Some(address), Some(Fill::Colour(colour))) => {
+                result.colours.insert(address.to_uppercase(), colour.clone());
+            },
+            (Some(address), Some(Fill::Unknown)) => {
+                result.skipped.insert(address.to_uppercase());
+            },
+            _ => (),
+        }
+    }
+    result
+}
+
+/// A warning about roster cells whose fills can't be matched to the colours in the config file, if any colours are
+/// configured
+pub fn skipped_warning(meanings: &HashMap<String, ColourMeaning>, skipped: usize) -> Option<String> {
+    (!meanings.is_empty() && skipped > 0)
+        .then(|| format!("{skipped} roster cells are filled with theme colours or patterns, which can't be matched to the colours in the config file"))
+}
+
+/// Give the events on each coloured day the category and marker configured for the colour
+pub fn annotate_events(meanings: &HashMap<String, ColourMeaning>, first_day_of_month: NaiveDate, colours: &[Option<&str>], events: &mut [CalendarEvent]) {
+    for (i, colour) in colours.iter().enumerate() {
+        let Some(meaning) = colour.and_then(|colour| meanings.iter().find(|(key, _)| normalise(key) == colour)).map(|(_, meaning)| meaning) else {
+            continue;
+        };
+        let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+        for event in events.iter_mut().filter(|event| event.is_on(date)) {
+            if let Some(marker) = &meaning.marker {
+                let (CalendarEvent::Normal { name, .. } | CalendarEvent::AllDay { name, .. } | CalendarEvent::MultiDay { name, .. }) = event;
+                let marker = format!("({marker})");
+                if !name.ends_with(&marker) {
+                    *name = format!("{name} {marker}");
+                }
+            }
+            if let Some(category) = &meaning.category {
+                event.add_category(category);
+            }
+        }
+    }
+}
+
+/// The colour of a solid fill. Palette colours are looked up, but theme colours depend on the workbook's
+/// theme and tint, so they're unknown
+fn fill_colour(fill: &str, palette: &[String]) -> Fill {
+    let Some((tag, pattern)) = elements(fill, "patternFill").next() else {
+        return Fill::Unknown;
+    };
+    match attribute(tag, "patternType").as_deref() {
+        None | Some("none") => return Fill::None,
+        Some("solid") => (),
+        Some(_) => return Fill::Unknown,
+    }
+    let Some(colour) = tags(pattern, "fgColor").next() else {
+        return Fill::Unknown;
+    };
+    if let Some(rgb) = attribute(colour, "rgb") {
+        return Fill::Colour(normalise(&rgb));
+    }
+    match attribute(colour, "indexed").and_then(|index| palette.get(index.parse::<usize>().ok()?)) {
+        Some(rgb) => Fill::Colour(rgb.clone()),
+        None => Fill::Unknown,
+    }
+}
+
+/// Colours as six upper-case hex digits. Excel writes them with an alpha channel first, e.g. "FFFFFF00"
+fn normalise(colour: &str) -> String {
+    let colour = colour.trim().trim_start_matches('#').to_uppercase();
+    match colour.len() {
+        8 => colour[2..].to_string(),
+        _ => colour,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLES: &str = r#"<styleSheet><fills count="5">
+        <fill><patternFill patternType="none"/></fill>
+        <fill><patternFill patternType="gray125"/></fill>
+        <fill><patternFill patternType="solid"><fgColor rgb="FFFFFF00"/><bgColor indexed="64"/></patternFill></fill>
+        <fill><patternFill patternType="solid"><fgColor indexed="10"/></patternFill></fill>
+        <fill><patternFill patternType="solid"><fgColor theme="9" tint="0.5999"/></patternFill></fill>
+        </fills>
+        <cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="2"/></cellStyleXfs>
+        <cellXfs count="5"><xf numFmtId="0" fontId="0" fillId="0" xfId="0"/><xf fillId="2" applyFill="1"><alignment horizontal="center"/></xf><xf fillId="3"/><xf fillId="4"/><xf fillId="1"/></cellXfs>
+        </styleSheet>"#;
+
+    #[test]
+    fn cells_get_their_fill_colours() {
+        let sheet = r#"<worksheet><sheetData><row r="1">
+            <c r="A1" t="s" s="1"><v>0</v></c>
+            <c r="B1" s="2"/>
+            <c r="C1" s="3"><v>4</v></c>
+            <c r="D1" t="inlineStr"><is><t>N</t></is></c>
+            <c r="E1" s="4"/>
+            <c r="F1" s="0"/>
+            </row></sheetData></worksheet>"#;
+        let fills = parse(STYLES, sheet);
+        assert_eq!(fills.colours.get("A1").map(String::as_str), Some("FFFF00"));
+        assert_eq!(fills.colours.get("B1").map(String::as_str), Some("FF0000"));
+        assert_eq!(fills.colours.len(), 2);
+        // C1 is a theme colour and E1 a pattern
+        assert_eq!(fills.skipped, HashSet::from([String::from("C1"), String::from("E1")]));
+    }
+
+    #[test]
+    fn skipped_fills_are_only_mentioned_with_colours_configured() {
+        let meanings = HashMap::from([(String::from("FFFF00"), ColourMeaning::default())]);
+        assert_eq!(skipped_warning(&HashMap::new(), 3), None);
+        assert_eq!(skipped_warning(&meanings, 0), None);
+        assert!(skipped_warning(&meanings, 3).is_some_and(|warning| warning.starts_with("3 roster cells")));
+    }
+
+    #[test]
+    fn custom_palettes_replace_the_default() {
+        let styles = STYLES.replace("<styleSheet>", r#"<styleSheet><colors><indexedColors><rgbColor rgb="FF000000"/><rgbColor rgb="FFFFFFFF"/><rgbColor rgb="FF112233"/><rgbColor rgb="FF112233"/><rgbColor rgb="FF112233"/><rgbColor rgb="FF112233"/><rgbColor rgb="FF112233"/><rgbColor rgb="FF112233"/><rgbColor rgb="FF112233"/><rgbColor rgb="FF112233"/><rgbColor rgb="FF00B050"/></indexedColors></colors>"#);
+        let fills = parse(&styles, r#"<c r="B1" s="2"/>"#);
+        assert_eq!(fills.colours.get("B1").map(String::as_str), Some("00B050"));
+    }
+
+    #[test]
+    fn colours_are_normalised() {
+        assert_eq!(normalise("FFFFFF00"), "FFFF00");
+        assert_eq!(normalise("#ff0000"), "FF0000");
+    }
+}
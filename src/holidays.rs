@@ -0,0 +1,256 @@
+//! UK bank holidays. The regular holidays are calculated from the rules that set them, with a table of
+//! the one-off changes (jubilees, royal events and moved holidays) since 2000. Further holidays can be
+//! loaded from text files with one holiday per line, e.g.
+//!
+//! ```text
+//! # Comments and blank lines are ignored
+//! 2025-07-14 Local holiday
+//! ```
+
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use serde::Deserialize;
+
+use crate::{config::HolidayConfig, roster::CalendarEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Region {
+    EnglandAndWales,
+    Scotland,
+    NorthernIreland,
+    /// No bundled holidays, only those loaded from files
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+}
+
+/// A one-off change to the regular holidays, applied in all regions
+struct OneOffChange {
+    year: i32,
+    /// The (month, day) of a regular holiday that didn't happen
+    remove: Option<(u32, u32)>,
+    /// The (month, day, name) of a holiday that was added or moved
+    add: Option<(u32, u32, &'static str)>,
+}
+
+const ONE_OFF_CHANGES: &[OneOffChange] = &[
+    OneOffChange { year: 2002, remove: Some((5, 27)), add: Some((6, 4, "Spring bank holiday")) },
+    OneOffChange { year: 2002, remove: None, add: Some((6, 3, "Golden Jubilee bank holiday")) },
+    OneOffChange { year: 2011, remove: None, add: Some((4, 29, "Royal wedding")) },
+    OneOffChange { year: 2012, remove: Some((5, 28)), add: Some((6, 4, "Spring bank holiday")) },
+    OneOffChange { year: 2012, remove: None, add: Some((6, 5, "Diamond Jubilee bank holiday")) },
+    OneOffChange { year: 2020, remove: Some((5, 4)), add: Some((5, 8, "Early May bank holiday (VE day)")) },
+    OneOffChange { year: 2022, remove: Some((5, 30)), add: Some((6, 2, "Spring bank holiday")) },
+    OneOffChange { year: 2022, remove: None, add: Some((6, 3, "Platinum Jubilee bank holiday")) },
+    OneOffChange { year: 2022, remove: None, add: Some((9, 19, "Bank holiday for the State Funeral of Queen Elizabeth II")) },
+    OneOffChange { year: 2023, remove: None, add: Some((5, 8, "Bank holiday for the coronation of King Charles III")) },
+];
+
+
+/// Load the holidays for the year before, the year of and the year after `year`, so that duties and leave
+/// that cross the new year are covered
+pub fn load(config: &HolidayConfig, year: i32) -> anyhow::Result<Vec<Holiday>> {
+    let mut holidays = Vec::new();
+    for year in (year - 1)..=(year + 1) {
+        holidays.extend(bank_holidays(config.region, year));
+    }
+    for path in &config.files {
+        holidays.extend(load_file(path)?);
+    }
+    holidays.sort_by_key(|holiday| holiday.date);
+    Ok(holidays)
+}
+
+pub fn find(holidays: &[Holiday], date: NaiveDate) -> Option<&Holiday> {
+    holidays.iter().find(|holiday| holiday.date == date)
+}
+
+/// Add the name of the holiday to any shifts or all-day events that fall on one
+pub fn annotate_events(events: &mut [CalendarEvent], holidays: &[Holiday]) {
+    for event in events {
+        let (name, date) = match event {
+            CalendarEvent::Normal { name, start, .. } => (name, start.date()),
+            CalendarEvent::AllDay { name, date, .. } => (name, *date),
+            CalendarEvent::MultiDay { .. } => continue,
+        };
+        if let Some(holiday) = find(holidays, date) {
+            *name = format!("{name} ({})", holiday.name);
+        }
+    }
+}
+
+/// All-day events for each holiday in the month
+pub fn holiday_markers(first_day_of_month: NaiveDate, num_days: usize, holidays: &[Holiday]) -> Vec<CalendarEvent> {
+    let last_day_of_month = first_day_of_month.checked_add_days(Days::new(num_days as u64 - 1)).unwrap();
+    holidays.iter()
+        .filter(|holiday| (first_day_of_month..=last_day_of_month).contains(&holiday.date))
+        .map(|holiday| CalendarEvent::AllDay { name: holiday.name.clone(), date: holiday.date, description: None, categories: Vec::new() })
+        .collect()
+}
+
+pub fn load_file(path: impl AsRef<Path>) -> anyhow::Result<Vec<Holiday>> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path).with_context(|| format!("Unable to read holiday file {}", path.display()))?;
+    let mut holidays = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (date, name) = line.split_once(char::is_whitespace).unwrap_or((line, "Holiday"));
+        let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+            bail!("Invalid date on line {} of holiday file {}. Dates must be in the form YYYY-MM-DD", i + 1, path.display());
+        };
+        holidays.push(Holiday { date, name: name.trim().to_string() });
+    }
+    Ok(holidays)
+}
+
+/// The bank holidays for a region in a given year
+pub fn bank_holidays(region: Region, year: i32) -> Vec<Holiday> {
+    if let Region::None = region {
+        return Vec::new();
+    }
+    let easter = easter_sunday(year);
+    let mut holidays = Vec::new();
+    let mut add = |date: NaiveDate, name: &str| holidays.push(Holiday { date, name: name.to_string() });
+
+    // Fixed-date holidays move to the next weekday that isn't already a holiday if they fall at a weekend
+    let mut fixed: Vec<(u32, u32, &str)> = vec![(1, 1, "New Year's Day")];
+    if let Region::Scotland = region {
+        fixed.push((1, 2, "2nd January"));
+    }
+    if let Region::NorthernIreland = region {
+        fixed.push((3, 17, "St Patrick's Day"));
+        fixed.push((7, 12, "Battle of the Boyne (Orangemen's Day)"));
+    }
+    if let Region::Scotland = region {
+        fixed.push((11, 30, "St Andrew's Day"));
+    }
+    fixed.push((12, 25, "Christmas Day"));
+    fixed.push((12, 26, "Boxing Day"));
+
+    let is_weekend = |date: NaiveDate| matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+    let fixed: Vec<(NaiveDate, &str)> = fixed.into_iter().map(|(month, day, name)| (NaiveDate::from_ymd_opt(year, month, day).unwrap(), name)).collect();
+    let mut taken: Vec<NaiveDate> = fixed.iter().map(|(date, _)| *date).filter(|date| !is_weekend(*date)).collect();
+    for (date, name) in fixed {
+        if !is_weekend(date) {
+            add(date, name);
+            continue;
+        }
+        let mut substitute = date;
+        while is_weekend(substitute) || taken.contains(&substitute) {
+            substitute = substitute.succ_opt().unwrap();
+        }
+        taken.push(substitute);
+        add(substitute, &format!("{name} (substitute day)"));
+    }
+
+    add(easter.checked_sub_days(Days::new(2)).unwrap(), "Good Friday");
+    if region != Region::Scotland {
+        add(easter.checked_add_days(Days::new(1)).unwrap(), "Easter Monday");
+    }
+    add(NaiveDate::from_weekday_of_month_opt(year, 5, Weekday::Mon, 1).unwrap(), "Early May bank holiday");
+    add(last_monday(year, 5), "Spring bank holiday");
+    match region {
+        Region::Scotland => add(NaiveDate::from_weekday_of_month_opt(year, 8, Weekday::Mon, 1).unwrap(), "Summer bank holiday"),
+        _ => add(last_monday(year, 8), "Summer bank holiday"),
+    }
+
+    for change in ONE_OFF_CHANGES.iter().filter(|change| change.year == year) {
+        if let Some((month, day)) = change.remove {
+            let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+            holidays.retain(|holiday| holiday.date != date);
+        }
+        if let Some((month, day, name)) = &change.add {
+            holidays.push(Holiday { date: NaiveDate::from_ymd_opt(year, *month, *day).unwrap(), name: name.to_string() });
+        }
+    }
+
+    holidays.sort_by_key(|holiday| holiday.date);
+    holidays
+}
+
+fn last_monday(year: i32, month: u32) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Mon, 5)
+        .unwrap_or_else(|| NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Mon, 4).unwrap())
+}
+
+/// Easter Sunday in the Gregorian calendar (the anonymous Gregorian algorithm)
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn name_on(holidays: &[Holiday], date: NaiveDate) -> Option<&str> {
+        find(holidays, date).map(|holiday| holiday.name.as_str())
+    }
+
+    #[test]
+    fn easter() {
+        assert_eq!(easter_sunday(2024), date(2024, 3, 31));
+        assert_eq!(easter_sunday(2025), date(2025, 4, 20));
+        let holidays = bank_holidays(Region::EnglandAndWales, 2025);
+        assert_eq!(name_on(&holidays, date(2025, 4, 18)), Some("Good Friday"));
+        assert_eq!(name_on(&holidays, date(2025, 4, 21)), Some("Easter Monday"));
+        assert_eq!(name_on(&bank_holidays(Region::Scotland, 2025), date(2025, 4, 21)), None);
+    }
+
+    #[test]
+    fn christmas_on_a_saturday() {
+        // Christmas 2021 was a Saturday, so both Christmas and Boxing Day moved to the following weekdays
+        let holidays = bank_holidays(Region::EnglandAndWales, 2021);
+        assert_eq!(name_on(&holidays, date(2021, 12, 25)), None);
+        assert_eq!(name_on(&holidays, date(2021, 12, 26)), None);
+        assert_eq!(name_on(&holidays, date(2021, 12, 27)), Some("Christmas Day (substitute day)"));
+        assert_eq!(name_on(&holidays, date(2021, 12, 28)), Some("Boxing Day (substitute day)"));
+    }
+
+    #[test]
+    fn christmas_on_a_sunday() {
+        let holidays = bank_holidays(Region::EnglandAndWales, 2022);
+        assert_eq!(name_on(&holidays, date(2022, 12, 26)), Some("Boxing Day"));
+        assert_eq!(name_on(&holidays, date(2022, 12, 27)), Some("Christmas Day (substitute day)"));
+    }
+
+    #[test]
+    fn one_off_holidays() {
+        let holidays = bank_holidays(Region::EnglandAndWales, 2022);
+        assert_eq!(name_on(&holidays, date(2022, 5, 30)), None);
+        assert_eq!(name_on(&holidays, date(2022, 6, 2)), Some("Spring bank holiday"));
+        assert_eq!(name_on(&holidays, date(2022, 6, 3)), Some("Platinum Jubilee bank holiday"));
+        assert_eq!(name_on(&holidays, date(2022, 9, 19)), Some("Bank holiday for the State Funeral of Queen Elizabeth II"));
+
+        let holidays = bank_holidays(Region::Scotland, 2023);
+        assert_eq!(name_on(&holidays, date(2023, 5, 8)), Some("Bank holiday for the coronation of King Charles III"));
+        assert_eq!(name_on(&holidays, date(2023, 5, 29)), Some("Spring bank holiday"));
+    }
+}
@@ -0,0 +1,111 @@
+use std::{fs::File, io::{BufWriter, Write}, path::Path};
+
+use anyhow::Context;
+use chrono::{Datelike, Days, NaiveDate};
+
+use crate::roster::{self, CalendarEvent, EventType, ShiftCategory};
+
+const STYLE: &str = r#"
+body { font-family: Arial, Helvetica, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.6em; margin-bottom: 0.5em; }
+table.month { border-collapse: collapse; width: 100%; table-layout: fixed; }
+table.month th { background: #444; color: #fff; padding: 0.4em; font-weight: normal; }
+table.month td { border: 1px solid #999; height: 5.5em; vertical-align: top; padding: 0.3em; }
+table.month td.empty { background: #f4f4f4; }
+.date { font-weight: bold; font-size: 0.9em; }
+.event { font-size: 0.9em; margin-top: 0.2em; }
+.times { font-size: 0.8em; color: #444; }
+.legend { margin-top: 1em; }
+.legend span { display: inline-block; padding: 0.2em 0.8em; margin-right: 0.5em; border: 1px solid #999; }
+.early { background: #fff3b0; }
+.day { background: #c8e6c9; }
+.late { background: #ffcc80; }
+.night { background: #b3c7f2; }
+.leave { background: #e1bee7; }
+.dayoff { background: #ffffff; }
+.other { background: #e0e0e0; }
+@media print {
+  @page { size: A4 landscape; margin: 1cm; }
+  body { margin: 0; }
+  * { -webkit-print-color-adjust: exact; print-color-adjust: exact; }
+}
+"#;
+
+
+/// Write a printable month view of the roster as a single self-contained HTML file
+pub fn save_file(path: impl AsRef<Path>, person: &str, first_day_of_month: NaiveDate, days: &[EventType], events: &[CalendarEvent]) -> anyhow::Result<()> {
+    let title = format!("{} - {}", person, first_day_of_month.format("%B %Y"));
+    let events_by_day = roster::events_by_day(first_day_of_month, days.len(), events);
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n", escape(&title)));
+    html.push_str(&format!("<h1>{}</h1>\n", escape(&title)));
+
+    html.push_str("<table class=\"month\">\n<tr>");
+    for weekday in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"] {
+        html.push_str(&format!("<th>{weekday}</th>"));
+    }
+    html.push_str("</tr>\n<tr>");
+
+    // Pad the first week so that the 1st falls under the right weekday
+    let leading_blanks = first_day_of_month.weekday().num_days_from_monday() as usize;
+    for _ in 0..leading_blanks {
+        html.push_str("<td class=\"empty\"></td>");
+    }
+
+    for (i, (day, day_events)) in days.iter().zip(&events_by_day).enumerate() {
+        let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+        if i > 0 && date.weekday().num_days_from_monday() == 0 {
+            html.push_str("</tr>\n<tr>");
+        }
+
+        // Days off within a block of annual leave take on the leave colour
+        let category = match day.category() {
+            ShiftCategory::DayOff if !day_events.is_empty() => ShiftCategory::Leave,
+            category => category,
+        };
+        html.push_str(&format!("<td class=\"{}\"><div class=\"date\">{}</div>", css_class(category), date.day()));
+        for event in day_events {
+            html.push_str(&format!("<div class=\"event\">{}</div>", escape(event.name())));
+            if let CalendarEvent::Normal { start, end, .. } = event {
+                html.push_str(&format!("<div class=\"times\">{} - {}</div>", start.format("%H:%M"), end.format("%H:%M")));
+            }
+        }
+        html.push_str("</td>");
+    }
+
+    let trailing_blanks = (7 - (leading_blanks + days.len()) % 7) % 7;
+    for _ in 0..trailing_blanks {
+        html.push_str("<td class=\"empty\"></td>");
+    }
+    html.push_str("</tr>\n</table>\n");
+
+    html.push_str("<div class=\"legend\">");
+    for category in ShiftCategory::ALL {
+        html.push_str(&format!("<span class=\"{}\">{category}</span>", css_class(category)));
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+
+    let file = File::create(path).context("Unable to create file")?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(html.as_bytes()).context("Unable to write file")?;
+    writer.flush().context("Unable to write file")?;
+    Ok(())
+}
+
+fn css_class(category: ShiftCategory) -> &'static str {
+    match category {
+        ShiftCategory::Early => "early",
+        ShiftCategory::Day => "day",
+        ShiftCategory::Late => "late",
+        ShiftCategory::Night => "night",
+        ShiftCategory::Leave => "leave",
+        ShiftCategory::DayOff => "dayoff",
+        ShiftCategory::Other => "other",
+    }
+}
+
+pub fn escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -1,6 +1,7 @@
-use chrono::{Days, NaiveDate, NaiveDateTime, Utc};
+use anyhow::{bail, Context};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use guid_create::GUID;
-use ics::{components::Property, parameters::{TzIDParam, Value}, properties::{Attendee, CalScale, Description, DtEnd, DtStart, Method, RRule, Sequence, Status, Summary, Trigger, TzName}, Alarm, Daylight, Event, ICalendar, Standard, TimeZone};
+use ics::{components::Property, parameters::{TzIDParam, Value}, properties::{Attendee, CalScale, Categories, Description, DtEnd, DtStart, Method, RRule, Sequence, Status, Summary, Trigger, TzName}, escape_text, Alarm, Daylight, Event, ICalendar, Standard, TimeZone};
 
 use crate::{roster::CalendarEvent, util::ReminderAdvance};
 const PRODID: &str = "-//CMERLIN//ROSTER TO ICS//EN";
@@ -23,6 +24,8 @@ const DAYLIGHT_OFFSET_TO: &str = "+0100";
 const DATE_FORMAT_STRING: &str = "%Y%m%d";
 const DATE_TIME_FORMAT_STRING: &str = "%Y%m%dT%H%M%S";
 const DATE_TIME_Z_FORMAT_STRING: &str = "%Y%m%dT%H%M%SZ";
+// Text properties that can hold a list of values separated by commas (RFC 5545 section 3.8.1)
+const MULTI_VALUED_PROPERTIES: &[&str] = &["CATEGORIES", "RESOURCES"];
 
 
 pub struct CalendarSettings {
@@ -67,26 +70,35 @@ pub fn new_event<'a>(settings: &'a CalendarSettings, calendar_event: &'a Calenda
 
     let mut event = Event::new(GUID::rand().to_string(), &settings.now_string);
 
-    let desc = match calendar_event {
-        CalendarEvent::Normal { name, start, end } => {
+    let name = match calendar_event {
+        CalendarEvent::Normal { name, start, end, .. } => {
             event.push(start.start_time_fmt());
             event.push(end.end_time_fmt());
             name
         },
-        CalendarEvent::AllDay { name, date } => {
+        CalendarEvent::AllDay { name, date, .. } => {
             event.push(date.start_date_fmt());
             name
         },
-        CalendarEvent::MultiDay { name, start, end } => {
+        CalendarEvent::MultiDay { name, start, end, .. } => {
             event.push(start.start_date_fmt());
             event.push(end.end_date_fmt());
             name
         },
     };
 
+    // Names can contain commas and semicolons, e.g. "N: SMITH, JONES"
+    let desc = escape_text(name.as_str());
     event.push(Sequence::new("0"));
     event.push(Summary::new(desc.clone()));
     event.push(Status::confirmed());
+    if let Some(description) = calendar_event.description() {
+        event.push(Description::new(escape_text(description)));
+    }
+    if !calendar_event.categories().is_empty() {
+        let categories: Vec<String> = calendar_event.categories().iter().map(|category| escape_text(category.as_str()).into_owned()).collect();
+        event.push(Categories::new(categories.join(",")));
+    }
 
 
     if let Some(email) = &settings.email_address {
@@ -107,22 +119,22 @@ pub fn new_event<'a>(settings: &'a CalendarSettings, calendar_event: &'a Calenda
 
 
 trait IcsDateTimeFormat {
-    fn start_time_fmt(&self) -> DtStart;
-    fn end_time_fmt(&self) -> DtEnd;
+    fn start_time_fmt(&self) -> DtStart<'_>;
+    fn end_time_fmt(&self) -> DtEnd<'_>;
 }
 
 trait IcsDateFormat {
-    fn start_date_fmt(&self) -> DtStart;
-    fn end_date_fmt(&self) -> DtEnd;
+    fn start_date_fmt(&self) -> DtStart<'_>;
+    fn end_date_fmt(&self) -> DtEnd<'_>;
 }
 
 impl IcsDateTimeFormat for NaiveDateTime {
-    fn start_time_fmt(&self) -> DtStart {
+    fn start_time_fmt(&self) -> DtStart<'_> {
         let mut start = DtStart::new(self.format(DATE_TIME_FORMAT_STRING).to_string());
         start.add(TzIDParam::new(TZ_STRING));
         start
     }
-    fn end_time_fmt(&self) -> DtEnd {
+    fn end_time_fmt(&self) -> DtEnd<'_> {
         let mut end = DtEnd::new(self.format(DATE_TIME_FORMAT_STRING).to_string());
         end.add(TzIDParam::new(TZ_STRING));
         end
@@ -130,14 +142,291 @@ impl IcsDateTimeFormat for NaiveDateTime {
 }
 
 impl IcsDateFormat for NaiveDate {
-    fn start_date_fmt(&self) -> DtStart {
+    fn start_date_fmt(&self) -> DtStart<'_> {
         let mut start = DtStart::new(self.format(DATE_FORMAT_STRING).to_string());
         start.add(Value::DATE);
         start
     }
-    fn end_date_fmt(&self) -> DtEnd {
+    fn end_date_fmt(&self) -> DtEnd<'_> {
         let mut end = DtEnd::new(self.format(DATE_FORMAT_STRING).to_string());
         end.add(Value::DATE);
         end
     }
-}
\ No newline at end of file
+}
+
+/// A component read back from the text form of a calendar, used to re-emit it in other representations
+pub struct ParsedComponent {
+    pub name: String,
+    pub properties: Vec<ParsedProperty>,
+    pub components: Vec<ParsedComponent>,
+}
+
+pub struct ParsedProperty {
+    pub name: String,
+    pub parameters: Vec<(String, String)>,
+    pub value_type: ValueType,
+    /// Most properties have one value, but some can have a list, e.g. CATEGORIES:Overtime,Changed
+    pub values: Vec<String>,
+}
+
+/// The value types that can appear in calendars produced by this program, as named in RFC 5545
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Text,
+    Integer,
+    Date,
+    DateTime,
+    Duration,
+    Recur,
+    UtcOffset,
+    CalAddress,
+    Unknown,
+}
+
+impl ValueType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueType::Text => "text",
+            ValueType::Integer => "integer",
+            ValueType::Date => "date",
+            ValueType::DateTime => "date-time",
+            ValueType::Duration => "duration",
+            ValueType::Recur => "recur",
+            ValueType::UtcOffset => "utc-offset",
+            ValueType::CalAddress => "cal-address",
+            ValueType::Unknown => "unknown",
+        }
+    }
+
+    fn for_property(name: &str, value_param: Option<&str>) -> Self {
+        match value_param {
+            Some("DATE") => return ValueType::Date,
+            Some("DATE-TIME") => return ValueType::DateTime,
+            _ => (),
+        }
+        match name {
+            "DTSTART" | "DTEND" | "DTSTAMP" | "CREATED" | "LAST-MODIFIED" | "RECURRENCE-ID" => ValueType::DateTime,
+            "SEQUENCE" | "PRIORITY" | "REPEAT" => ValueType::Integer,
+            "TRIGGER" | "DURATION" => ValueType::Duration,
+            "RRULE" => ValueType::Recur,
+            "TZOFFSETFROM" | "TZOFFSETTO" => ValueType::UtcOffset,
+            "ATTENDEE" | "ORGANIZER" => ValueType::CalAddress,
+            name if name.starts_with("X-") => ValueType::Unknown,
+            _ => ValueType::Text,
+        }
+    }
+}
+
+/// Parse a calendar back out of its text form. Values are converted to the formats used by
+/// jCal (RFC 7265) and xCal (RFC 6321), e.g. 20250301T220000 becomes 2025-03-01T22:00:00, and the
+/// VALUE parameter is dropped as the value type is carried separately.
+pub fn parse_calendar(calendar: &ICalendar) -> anyhow::Result<ParsedComponent> {
+    let text = calendar.to_string();
+    // Unfold content lines
+    let text = text.replace("\r\n ", "").replace("\r\n\t", "");
+
+    let mut stack: Vec<ParsedComponent> = Vec::new();
+    for line in text.lines().filter(|line| !line.is_empty()) {
+        let property = parse_content_line(line).with_context(|| format!("Unable to parse calendar line '{line}'"))?;
+        match property.name.as_str() {
+            "BEGIN" => stack.push(ParsedComponent { name: property.values.concat(), properties: Vec::new(), components: Vec::new() }),
+            "END" => {
+                let component = stack.pop().context("Unbalanced END in calendar")?;
+                match stack.last_mut() {
+                    Some(parent) => parent.components.push(component),
+                    None => return Ok(component),
+                }
+            },
+            _ => stack.last_mut().context("Property outside of a component in calendar")?.properties.push(property),
+        }
+    }
+    bail!("Calendar ended without END:VCALENDAR")
+}
+
+fn parse_content_line(line: &str) -> anyhow::Result<ParsedProperty> {
+    // Find the colon separating the name and parameters from the value, skipping any inside quoted parameter values
+    let mut in_quotes = false;
+    let colon = line.char_indices().find(|(_, c)| {
+        if *c == '"' {
+            in_quotes = !in_quotes;
+        }
+        *c == ':' && !in_quotes
+    }).map(|(i, _)| i).context("Missing ':'")?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+
+    let mut parts = head.split(';');
+    let name = parts.next().unwrap().to_uppercase();
+    let mut parameters = Vec::new();
+    let mut value_param = None;
+    for part in parts {
+        let (key, param_value) = part.split_once('=').context("Missing '=' in parameter")?;
+        let key = key.to_uppercase();
+        let param_value = param_value.trim_matches('"').to_string();
+        if key == "VALUE" {
+            value_param = Some(param_value.to_uppercase());
+        } else {
+            parameters.push((key, param_value));
+        }
+    }
+
+    let value_type = ValueType::for_property(&name, value_param.as_deref());
+    let values = match MULTI_VALUED_PROPERTIES.contains(&name.as_str()) {
+        true => split_unescaped_commas(value),
+        false => vec![value],
+    };
+    let values = values.into_iter().map(|value| match value_type {
+        ValueType::Date => format_date(value),
+        ValueType::DateTime => format_date_time(value),
+        ValueType::UtcOffset => format_utc_offset(value),
+        ValueType::Text => unescape_text(value),
+        _ => value.to_string(),
+    }).collect();
+    Ok(ParsedProperty { name, parameters, value_type, values })
+}
+
+/// Split a list of text values on the commas that aren't escaped with a backslash
+fn split_unescaped_commas(value: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                values.push(&value[start..i]);
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    values.push(&value[start..]);
+    values
+}
+
+fn format_date(value: &str) -> String {
+    match NaiveDate::parse_from_str(value, DATE_FORMAT_STRING) {
+        Ok(date) => date.format("%Y-%m-%d").to_string(),
+        Err(_) => value.to_string(),
+    }
+}
+
+fn format_date_time(value: &str) -> String {
+    let (value, utc) = match value.strip_suffix('Z') {
+        Some(value) => (value, "Z"),
+        None => (value, ""),
+    };
+    match NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT_STRING) {
+        Ok(date_time) => format!("{}{utc}", date_time.format("%Y-%m-%dT%H:%M:%S")),
+        Err(_) => format!("{value}{utc}"),
+    }
+}
+
+fn format_utc_offset(value: &str) -> String {
+    if value.len() == 5 {
+        format!("{}:{}", &value[..3], &value[3..])
+    } else {
+        value.to_string()
+    }
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Split a recurrence rule such as FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU into its lower-case parts
+pub fn recur_parts(value: &str) -> Vec<(String, Vec<String>)> {
+    value.split(';')
+        .filter_map(|part| part.split_once('='))
+        .map(|(key, values)| (key.to_lowercase(), values.split(',').map(String::from).collect()))
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn calendar_text(events: &[CalendarEvent]) -> String {
+        let (mut calendar, settings) = new_calendar(None, Some(String::from("someone@example.com")), ReminderAdvance::MinutesBefore(60));
+        for event in events {
+            calendar.add_event(new_event(&settings, event));
+        }
+        calendar.to_string().replace("\r\n ", "")
+    }
+
+    /// A calendar with alarms and one night shift with a comma in its name and categories, read back from text
+    pub fn parsed_calendar() -> ParsedComponent {
+        let (mut calendar, settings) = new_calendar(None, Some(String::from("someone@example.com")), ReminderAdvance::MinutesBefore(60));
+        let start = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap().and_hms_opt(22, 0, 0).unwrap();
+        let event = CalendarEvent::Normal {
+            name: String::from("N: SMITH, JONES"),
+            start,
+            end: start + chrono::TimeDelta::hours(8),
+            description: None,
+            categories: vec![String::from("Overtime"), String::from("Changed, late")],
+        };
+        calendar.add_event(new_event(&settings, &event));
+        parse_calendar(&calendar).unwrap()
+    }
+
+    /// The first component with the name, searching depth first
+    pub fn find<'a>(component: &'a ParsedComponent, name: &str) -> Option<&'a ParsedComponent> {
+        if component.name == name {
+            return Some(component);
+        }
+        component.components.iter().find_map(|component| find(component, name))
+    }
+
+    pub fn property<'a>(component: &'a ParsedComponent, name: &str) -> &'a ParsedProperty {
+        component.properties.iter().find(|property| property.name == name).unwrap()
+    }
+
+    #[test]
+    fn summaries_are_escaped() {
+        let date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let event = CalendarEvent::AllDay { name: String::from("N: SMITH, JONES; PATEL"), date, description: None, categories: Vec::new() };
+        let text = calendar_text(&[event]);
+        assert!(text.contains("\r\nSUMMARY:N: SMITH\\, JONES\\; PATEL\r\n"), "{text}");
+        assert!(!text.contains("SMITH, JONES"), "{text}");
+    }
+
+    #[test]
+    fn calendars_are_read_back() {
+        let calendar = parsed_calendar();
+        let event = find(&calendar, "VEVENT").unwrap();
+        assert_eq!(property(event, "SUMMARY").values, ["N: SMITH, JONES"]);
+        assert_eq!(property(event, "CATEGORIES").values, ["Overtime", "Changed, late"]);
+        let start = property(event, "DTSTART");
+        assert_eq!((start.value_type, start.values.as_slice()), (ValueType::DateTime, [String::from("2025-03-01T22:00:00")].as_slice()));
+        assert_eq!(start.parameters, [(String::from("TZID"), String::from(TZ_STRING))]);
+
+        let alarms: Vec<&ParsedComponent> = event.components.iter().filter(|component| component.name == "VALARM").collect();
+        assert_eq!(alarms.len(), 2);
+        assert_eq!(property(alarms[0], "DESCRIPTION").values, ["N: SMITH, JONES"]);
+        assert_eq!(property(alarms[0], "TRIGGER").value_type, ValueType::Duration);
+
+        let rule = property(find(&calendar, "DAYLIGHT").unwrap(), "RRULE");
+        assert_eq!((rule.value_type, rule.values.as_slice()), (ValueType::Recur, [String::from(DAYLIGHT_RRULE)].as_slice()));
+        assert_eq!(recur_parts(&rule.values[0])[2], (String::from("byday"), vec![String::from("-1SU")]));
+    }
+
+    #[test]
+    fn lists_are_split_on_unescaped_commas() {
+        assert_eq!(split_unescaped_commas("Overtime,Changed\\, late"), ["Overtime", "Changed\\, late"]);
+        assert_eq!(split_unescaped_commas("One"), ["One"]);
+        assert_eq!(split_unescaped_commas("Back\\\\,slash"), ["Back\\\\", "slash"]);
+    }
+}
@@ -0,0 +1,82 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use anyhow::Context;
+use ics::ICalendar;
+use serde_json::{json, Map, Value};
+
+use crate::ics::{self as ical, ParsedComponent, ParsedProperty, ValueType};
+
+// Recurrence rule parts whose values are integers (RFC 7265 section 3.6.10)
+const INTEGER_RECUR_PARTS: &[&str] = &["count", "interval", "bysecond", "byminute", "byhour", "bymonthday", "byyearday", "byweekno", "bymonth", "bysetpos"];
+
+
+/// Write the calendar as jCal (RFC 7265)
+pub fn save_file(path: impl AsRef<Path>, calendar: &ICalendar) -> anyhow::Result<()> {
+    let calendar = ical::parse_calendar(calendar)?;
+    let file = File::create(path).context("Unable to create file")?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &component_value(&calendar)).context("Unable to write file")?;
+    Ok(())
+}
+
+fn component_value(component: &ParsedComponent) -> Value {
+    json!([
+        component.name.to_lowercase(),
+        component.properties.iter().map(property_value).collect::<Vec<_>>(),
+        component.components.iter().map(component_value).collect::<Vec<_>>(),
+    ])
+}
+
+fn property_value(property: &ParsedProperty) -> Value {
+    let parameters: Map<String, Value> = property.parameters.iter()
+        .map(|(key, value)| (key.to_lowercase(), Value::from(value.as_str())))
+        .collect();
+
+    // Each of a property's values is another element of its array
+    let values = property.values.iter().map(|value| match property.value_type {
+        ValueType::Integer => value.parse::<i64>().map(Value::from).unwrap_or_else(|_| Value::from(value.as_str())),
+        ValueType::Recur => {
+            let parts = ical::recur_parts(value).into_iter().map(|(key, values)| {
+                let mut values: Vec<Value> = values.iter().map(|value| match INTEGER_RECUR_PARTS.contains(&key.as_str()) {
+                    true => value.parse::<i64>().map(Value::from).unwrap_or_else(|_| Value::from(value.as_str())),
+                    false => Value::from(value.as_str()),
+                }).collect();
+                let value = if values.len() == 1 { values.remove(0) } else { Value::Array(values) };
+                (key, value)
+            });
+            Value::Object(parts.collect())
+        },
+        _ => Value::from(value.as_str()),
+    });
+
+    let mut array = vec![Value::from(property.name.to_lowercase()), Value::Object(parameters), Value::from(property.value_type.name())];
+    array.extend(values);
+    Value::Array(array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ics::tests::{find, parsed_calendar, property};
+
+    #[test]
+    fn properties_become_arrays() {
+        let calendar = parsed_calendar();
+        let event = find(&calendar, "VEVENT").unwrap();
+        assert_eq!(property_value(property(event, "CATEGORIES")), json!(["categories", {}, "text", "Overtime", "Changed, late"]));
+        assert_eq!(property_value(property(event, "SUMMARY")), json!(["summary", {}, "text", "N: SMITH, JONES"]));
+        assert_eq!(property_value(property(event, "DTSTART")), json!(["dtstart", {"tzid": "Europe/London"}, "date-time", "2025-03-01T22:00:00"]));
+        assert_eq!(property_value(property(event, "SEQUENCE")), json!(["sequence", {}, "integer", 0]));
+
+        let rule = property(find(&calendar, "DAYLIGHT").unwrap(), "RRULE");
+        assert_eq!(property_value(rule), json!(["rrule", {}, "recur", {"freq": "YEARLY", "bymonth": 3, "byday": "-1SU"}]));
+    }
+
+    #[test]
+    fn alarms_are_nested_in_events() {
+        let value = component_value(find(&parsed_calendar(), "VEVENT").unwrap());
+        let alarms = value[2].as_array().unwrap();
+        assert_eq!(alarms.len(), 2);
+        assert_eq!(alarms[0][0], "valarm");
+        assert!(alarms[0][1].as_array().unwrap().contains(&json!(["description", {}, "text", "N: SMITH, JONES"])));
+    }
+}
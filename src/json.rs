@@ -0,0 +1,105 @@
+//! JSON export of a person's parsed roster and the calendar events generated from it.
+//!
+//! `schema_version` is bumped whenever a field is removed or changes meaning. New fields may be added
+//! without bumping it, so consumers should ignore fields they don't recognise.
+//!
+//! ```text
+//! {
+//!   "schema_version": 1,
+//!   "person": "SMITH J",
+//!   "month": "2025-03",                      // YYYY-MM
+//!   "timezone": "Europe/London",             // IANA zone that all local times are in
+//!   "days": [                                // One entry per day of the month, in order
+//!     {
+//!       "date": "2025-03-01",
+//!       "code": "N",                         // The cell as it appears in the spreadsheet
+//!       "event_type": { "type": "N" },       // Resolved code. Unknown codes are { "type": "Other", "name", "hour_start", ... }
+//!                                            // and overtime is { "type": "Overtime", "shift": { "type": "D4" }, "code": "OT D4" }
+//!       "summary": "N shift"
+//!     }
+//!   ],
+//!   "events": [                              // The events that are written to the calendar. description may be null
+//!     { "kind": "normal", "name": "N shift", "start": "2025-03-01T22:00:00+00:00", "end": "2025-03-02T06:30:00+00:00", "description": null, "categories": [] },
+//!     { "kind": "all_day", "name": "DIL", "date": "2025-03-04", "description": null, "categories": [] },
+//!     { "kind": "multi_day", "name": "Annual leave", "start": "2025-03-10", "end": "2025-03-15", "description": null, "categories": [] }  // end is exclusive
+//!   ]
+//! }
+//! ```
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use anyhow::Context;
+use chrono::{DateTime, Days, FixedOffset, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use crate::{roster::{CalendarEvent, EventType}, util};
+
+pub const SCHEMA_VERSION: u32 = 1;
+const TZ_STRING: &str = "Europe/London";
+const MONTH_FORMAT_STRING: &str = "%Y-%m";
+
+#[derive(Serialize)]
+struct RosterJson<'a> {
+    schema_version: u32,
+    person: &'a str,
+    month: String,
+    timezone: &'a str,
+    days: Vec<DayJson<'a>>,
+    events: Vec<EventJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct DayJson<'a> {
+    date: NaiveDate,
+    code: &'a str,
+    event_type: &'a EventType,
+    summary: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum EventJson<'a> {
+    Normal { name: &'a str, start: DateTime<FixedOffset>, end: DateTime<FixedOffset>, description: Option<&'a str>, categories: &'a [String] },
+    AllDay { name: &'a str, date: NaiveDate, description: Option<&'a str>, categories: &'a [String] },
+    MultiDay { name: &'a str, start: NaiveDate, end: NaiveDate, description: Option<&'a str>, categories: &'a [String] },
+}
+
+impl<'a> From<&'a CalendarEvent> for EventJson<'a> {
+    fn from(calendar_event: &'a CalendarEvent) -> Self {
+        match calendar_event {
+            CalendarEvent::Normal { name, start, end, description, categories } => {
+                EventJson::Normal { name, start: with_offset(start), end: with_offset(end), description: description.as_deref(), categories }
+            },
+            CalendarEvent::AllDay { name, date, description, categories } => EventJson::AllDay { name, date: *date, description: description.as_deref(), categories },
+            CalendarEvent::MultiDay { name, start, end, description, categories } => {
+                EventJson::MultiDay { name, start: *start, end: *end, description: description.as_deref(), categories }
+            },
+        }
+    }
+}
+
+fn with_offset(local: &NaiveDateTime) -> DateTime<FixedOffset> {
+    local.and_local_timezone(util::london_utc_offset(local)).unwrap()
+}
+
+pub fn save_file(path: impl AsRef<Path>, person: &str, first_day_of_month: NaiveDate, codes: &[String], days: &[EventType], events: &[CalendarEvent]) -> anyhow::Result<()> {
+    let days = codes.iter().zip(days).enumerate().map(|(i, (code, event_type))| DayJson {
+        date: first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap(),
+        code,
+        event_type,
+        summary: event_type.to_string(),
+    }).collect();
+
+    let roster = RosterJson {
+        schema_version: SCHEMA_VERSION,
+        person,
+        month: first_day_of_month.format(MONTH_FORMAT_STRING).to_string(),
+        timezone: TZ_STRING,
+        days,
+        events: events.iter().map(EventJson::from).collect(),
+    };
+
+    let file = File::create(path).context("Unable to create file")?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &roster).context("Unable to write file")?;
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! A ledger of annual leave, days in lieu and sick days, kept as a JSON file. Each person's leave is stored
+//! by month and a month is replaced in full whenever it is recorded, so processing the same roster again
+//! doesn't count anything twice.
+
+use std::{collections::BTreeMap, fs::File, io::BufWriter, path::Path};
+
+use anyhow::{bail, Context};
+use chrono::{Datelike, Days, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::{args::{LeaveArgs, LeaveBalanceArgs, LeaveCommand, LeaveRecordArgs}, config::{self, LeaveConfig}, roster::EventType, util, xlsx};
+
+const LEDGER_VERSION: u32 = 1;
+const MONTH_FORMAT_STRING: &str = "%Y-%m";
+
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Ledger {
+    pub version: u32,
+    /// Leave by person, then by month as "YYYY-MM"
+    pub people: BTreeMap<String, BTreeMap<String, MonthLeave>>,
+}
+impl Default for Ledger {
+    fn default() -> Self {
+        Self { version: LEDGER_VERSION, people: BTreeMap::new() }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MonthLeave {
+    pub annual_leave: Vec<NaiveDate>,
+    pub days_in_lieu: Vec<NaiveDate>,
+    pub sick: Vec<NaiveDate>,
+}
+
+impl Ledger {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Ledger::default());
+        }
+        let file = File::open(path).with_context(|| format!("Unable to open leave ledger {}", path.display()))?;
+        let ledger: Ledger = serde_json::from_reader(file).with_context(|| format!("Invalid leave ledger {}", path.display()))?;
+        if ledger.version != LEDGER_VERSION {
+            bail!("Leave ledger {} has unsupported version {}", path.display(), ledger.version);
+        }
+        Ok(ledger)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path).with_context(|| format!("Unable to create leave ledger {}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self).context("Unable to write leave ledger")?;
+        Ok(())
+    }
+
+    /// Record a person's leave for a month, replacing anything previously recorded for that month
+    pub fn record_month(&mut self, name: &str, first_day_of_month: NaiveDate, days: &[EventType]) {
+        let mut month = MonthLeave::default();
+        for (i, day) in days.iter().enumerate() {
+            let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+            match day {
+                EventType::Leave => month.annual_leave.push(date),
+                EventType::DayInLieu => month.days_in_lieu.push(date),
+                EventType::Sick => month.sick.push(date),
+                _ => (),
+            }
+        }
+        self.people.entry(name.to_string()).or_default().insert(first_day_of_month.format(MONTH_FORMAT_STRING).to_string(), month);
+    }
+}
+
+pub fn run(args: &LeaveArgs) -> anyhow::Result<()> {
+    match &args.command {
+        LeaveCommand::Record(record_args) => record(record_args),
+        LeaveCommand::Balance(balance_args) => balance(balance_args),
+    }
+}
+
+fn record(args: &LeaveRecordArgs) -> anyhow::Result<()> {
+    let config = config::load(args.config.as_deref())?;
+    let roster = xlsx::open_roster(&args.roster)?;
+
+    let people = if args.all {
+        roster.people(args.watch.as_deref())?
+    } else {
+        let selected_name_index = util::select_name_from_user(&roster, "Whose leave do you wish to record?");
+        vec![roster.names[selected_name_index].clone()]
+    };
+
+    let mut ledger = Ledger::load(&config.leave.ledger)?;
+    for (row, name) in &people {
+        let days = roster.events_for_row(*row)?;
+        ledger.record_month(name, roster.first_day_of_month, &days);
+    }
+    ledger.save(&config.leave.ledger)?;
+    println!("Recorded leave for {} {} in {}", people.len(), if people.len() == 1 { "person" } else { "people" }, config.leave.ledger.display());
+    Ok(())
+}
+
+fn balance(args: &LeaveBalanceArgs) -> anyhow::Result<()> {
+    let config = config::load(args.config.as_deref())?;
+    let ledger = Ledger::load(&config.leave.ledger)?;
+    let date = args.date.unwrap_or_else(|| Local::now().date_naive());
+    let (year_start, year_end) = leave_year(&config.leave, date)?;
+
+    let names: Vec<&String> = match &args.name {
+        Some(name) => match ledger.people.get_key_value(name) {
+            Some((name, _)) => vec![name],
+            None => bail!("No leave recorded for {name}"),
+        },
+        None => ledger.people.keys().collect(),
+    };
+
+    println!("Leave year {} to {}", year_start.format("%d %B %Y"), year_end.checked_sub_days(Days::new(1)).unwrap().format("%d %B %Y"));
+    let name_width = names.iter().map(|name| name.len()).max().unwrap_or(0).max(4);
+    println!("{:<name_width$}  {:>9}  {:>7}  {:>9}  {:>4}  {:>4}", "Name", "Allowance", "AL used", "Remaining", "DIL", "Sick");
+    for name in names {
+        let in_year = |dates: &Vec<NaiveDate>| dates.iter().filter(|date| (year_start..year_end).contains(*date)).count();
+        let (mut annual_leave, mut days_in_lieu, mut sick) = (0, 0, 0);
+        for month in ledger.people[name].values() {
+            annual_leave += in_year(&month.annual_leave);
+            days_in_lieu += in_year(&month.days_in_lieu);
+            sick += in_year(&month.sick);
+        }
+        let allowance = config.leave.person_allowance_days.get(name).copied().unwrap_or(config.leave.allowance_days);
+        println!(
+            "{:<name_width$}  {:>9}  {:>7}  {:>9}  {:>4}  {:>4}",
+            name, allowance, annual_leave, allowance - annual_leave as f64, days_in_lieu, sick,
+        );
+    }
+    Ok(())
+}
+
+/// The start of the leave year containing `date`, and the start of the next one
+fn leave_year(config: &LeaveConfig, date: NaiveDate) -> anyhow::Result<(NaiveDate, NaiveDate)> {
+    let (month, day) = config.leave_year_start()?;
+    // A start of 29 February is 28 February in years that aren't leap years
+    let start_in_year = |year: i32| NaiveDate::from_ymd_opt(year, month, day).or_else(|| NaiveDate::from_ymd_opt(year, month, day - 1)).unwrap();
+    let start = match start_in_year(date.year()) {
+        start if start <= date => start,
+        _ => start_in_year(date.year() - 1),
+    };
+    Ok((start, start_in_year(start.year() + 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn config(leave_year_start: &str) -> LeaveConfig {
+        LeaveConfig { leave_year_start: leave_year_start.to_string(), ..LeaveConfig::default() }
+    }
+
+    #[test]
+    fn leave_years() {
+        assert_eq!(leave_year(&config("01-01"), date(2025, 6, 1)).unwrap(), (date(2025, 1, 1), date(2026, 1, 1)));
+        assert_eq!(leave_year(&config("04-01"), date(2025, 3, 31)).unwrap(), (date(2024, 4, 1), date(2025, 4, 1)));
+        assert_eq!(leave_year(&config("04-01"), date(2025, 4, 1)).unwrap(), (date(2025, 4, 1), date(2026, 4, 1)));
+        assert!(leave_year(&config("13-01"), date(2025, 4, 1)).is_err());
+    }
+
+    #[test]
+    fn leave_years_starting_on_29_february() {
+        // 2024 is a leap year, and 2023 and 2025 aren't
+        assert_eq!(leave_year(&config("02-29"), date(2024, 6, 1)).unwrap(), (date(2024, 2, 29), date(2025, 2, 28)));
+        assert_eq!(leave_year(&config("02-29"), date(2025, 2, 28)).unwrap(), (date(2025, 2, 28), date(2026, 2, 28)));
+        assert_eq!(leave_year(&config("02-29"), date(2024, 2, 28)).unwrap(), (date(2023, 2, 28), date(2024, 2, 29)));
+    }
+}
@@ -1,28 +1,61 @@
-use std::io::{self, Write};
-
 use anyhow::{bail, Context};
+use chrono::Datelike;
+use args::{Command, GenerateArgs, OutputFormat, RosterArgs};
 use clap::Parser;
+use csv::CsvLayout;
 use email_address::EmailAddress;
-use roster::EventType;
+use leave::Ledger;
 use util::ReminderAdvance;
 
 mod args;
 mod xlsx;
+mod detect;
+mod fills;
+mod comments;
+mod package;
 mod util;
 mod roster;
 mod ics;
+mod csv;
+mod json;
+mod jcal;
+mod xcal;
+mod html;
+mod show;
+mod config;
+mod check;
+mod stats;
+mod allowance;
+mod holidays;
+mod leave;
+mod team;
+mod coverage;
+mod swap;
+mod common;
+mod carpool;
 
 fn main() -> Result<(), anyhow::Error> {
     let args = args::Args::parse();
+    match args.command {
+        Some(Command::Show(show_args)) => show::run(&show_args),
+        Some(Command::Check(check_args)) => check::run(&check_args),
+        Some(Command::Stats(stats_args)) => stats::run(&stats_args),
+        Some(Command::Allowance(allowance_args)) => allowance::run(&allowance_args),
+        Some(Command::Leave(leave_args)) => leave::run(&leave_args),
+        Some(Command::Team(team_args)) => team::run(&team_args),
+        Some(Command::Coverage(coverage_args)) => coverage::run(&coverage_args),
+        Some(Command::Swap(swap_args)) => swap::run(&swap_args),
+        Some(Command::Common(common_args)) => common::run(&common_args),
+        Some(Command::Carpool(carpool_args)) => carpool::run(&carpool_args),
+        // Clap requires the roster and generate args when there is no subcommand
+        None => generate(&args.roster.unwrap(), args.generate.unwrap()),
+    }
+}
 
+fn generate(roster_args: &RosterArgs, args: GenerateArgs) -> Result<(), anyhow::Error> {
     // Validate some of the args
-    if let Some(year) = args.year {
-        if !(2000..2100).contains(&year) {
-            bail!("Invalid year ({year}). Must be between 2000 and 2099.");
-        }
-    }
     if let Some(email) = &args.email {
-        if !EmailAddress::is_valid(&email) {
+        if !EmailAddress::is_valid(email) {
             bail!("Invalid e-mail address: {email}");
         }
     }
@@ -31,69 +64,77 @@ fn main() -> Result<(), anyhow::Error> {
         None => ReminderAdvance::MinutesBefore(60),
     };
 
-    let first_day_of_month = util::get_first_day_of_month(&args)?;
-    let num_of_days_in_month = util::num_days_in_month(&first_day_of_month);
-    let worksheet = xlsx::get_roster_worksheet(&args.xlsx_path)?;
-    let (header_row, name_column) = xlsx::find_header_row_and_name_column(&worksheet)?;
-    let (first, last) = xlsx::find_first_and_last_day_columns(&worksheet, header_row, name_column, num_of_days_in_month)?;
-    let names = xlsx::enumerate_names(&worksheet, header_row, name_column);
+    let config = config::load(args.config.as_deref())?;
+    let roster = xlsx::open_roster(roster_args)?;
+    let first_day_of_month = roster.first_day_of_month;
+    let holidays = holidays::load(&config.holidays, first_day_of_month.year())?;
     
     // Prompt to select name
-    for (i, (_, name)) in names.iter().enumerate() {
-        println!("{:>2}: {}", i + 1, name);
-    }
-    let selected_name_index = loop {
-        print!("For whom do you wish to generate a calendar? Enter a number from 1 - {}: ", names.len());
-        io::stdout().flush().unwrap();
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        match input.trim().parse::<usize>() {
-            Ok(i) if (1..=names.len()).contains(&i) => break i - 1,
-            _ => {
-                println!("Invalid input.");
-                continue;
-            }
-        };
-    };
+    let selected_name_index = util::select_name_from_user(&roster, "For whom do you wish to generate a calendar?");
 
-    let mut days = xlsx::get_month_events_for_row(&worksheet, names[selected_name_index].0, first, last)?;
-    
-    let mut current_day = first_day_of_month.clone();
-    for day in days.iter_mut() {
-        if let EventType::Other { name, .. } = day {
-            let date = current_day.format("%d %B");
-            
-            let (hour_start, minute_start, hour_end, minute_end) = loop {
-                let (hour_start, minute_start) = util::get_time_from_user(&format!("When does event '{name}' on {date} start?"));
-                let (hour_end, minute_end) = util::get_time_from_user(&format!("When does event '{name}' on {date} end?"));
-                if hour_start * 100 + minute_start >= hour_end * 100 + minute_end {
-                    println!("End time must be after the start time.");
-                    continue;
-                }
-                break (hour_start, minute_start, hour_end, minute_end);
-            };
-
-            *day = EventType::Other { name: name.clone(), hour_start, minute_start, hour_end, minute_end };
-            
-        }
-        current_day = current_day.succ_opt().unwrap();
-    }
+    let (selected_row, selected_name) = &roster.names[selected_name_index];
+    let mut days = roster.events_for_row(*selected_row)?;
+    util::get_other_event_times_from_user(first_day_of_month, &mut days);
 
     // Convert to list of events
-    let event_list = roster::generate_calendar_events(first_day_of_month, days);
+    let mut event_list = roster::generate_calendar_events(first_day_of_month, &days);
+    comments::annotate_events(first_day_of_month, &roster.notes_for_row(*selected_row), &mut event_list);
+    if let Some(warning) = fills::skipped_warning(&config.colours, roster.skipped_fills()) {
+        eprintln!("Warning: {warning}");
+    }
+    fills::annotate_events(&config.colours, first_day_of_month, &roster.fills_for_row(*selected_row), &mut event_list);
+    if args.colleagues {
+        team::add_colleagues(&roster, *selected_row, &mut event_list);
+    }
+    if args.carpool {
+        carpool::add_suggestions(&config.carpool, &roster, *selected_row, selected_name, &mut event_list);
+    }
+    if args.holiday_names {
+        holidays::annotate_events(&mut event_list, &holidays);
+    }
+    if args.holiday_markers {
+        event_list.extend(holidays::holiday_markers(first_day_of_month, days.len(), &holidays));
+    }
     
 
-    // Initialise calendar
+    match args.format {
+        OutputFormat::Ics | OutputFormat::Jcal | OutputFormat::Xcal => {
+            // Initialise calendar
+
+            let (mut calendar, settings) = ics::new_calendar(args.name.clone(), args.email.clone(), reminder_advance);
+            // Populate calendar
+            for calendar_event in &event_list {
+                let event = ics::new_event(&settings, calendar_event);
+                calendar.add_event(event);
+            }
 
-    let (mut calendar, settings) = ics::new_calendar(args.name.clone(), args.email.clone(), reminder_advance);
-    // Populate calendar
-    for calendar_event in &event_list {
-        let event = ics::new_event(&settings, calendar_event);
-        calendar.add_event(event);
+            // Write calendar
+            match args.format {
+                OutputFormat::Jcal => jcal::save_file(args.output, &calendar)?,
+                OutputFormat::Xcal => xcal::save_file(args.output, &calendar)?,
+                _ => calendar.save_file(args.output).context("Unable to save file")?,
+            }
+        },
+        OutputFormat::Json => {
+            let codes = roster.codes_for_row(*selected_row)?;
+            json::save_file(args.output, selected_name, first_day_of_month, &codes, &days, &event_list)?;
+        },
+        OutputFormat::GoogleCsv | OutputFormat::OutlookCsv => {
+            let layout = if let OutputFormat::GoogleCsv = args.format { CsvLayout::Google } else { CsvLayout::Outlook };
+            let reminder = (args.email.is_some() || args.reminder_time.is_some()).then_some(&reminder_advance);
+            csv::save_file(args.output, layout, reminder, &event_list)?;
+        },
+        OutputFormat::Html => {
+            html::save_file(args.output, selected_name, first_day_of_month, &days, &event_list)?;
+        },
     }
 
-    // Write calendar
-    calendar.save_file(args.output_ics).context("Unable to save file")?;
+    // Only once the output has been written, so that a failed run doesn't leave anything in the ledger
+    if config.leave.record_on_generate {
+        let mut ledger = Ledger::load(&config.leave.ledger)?;
+        ledger.record_month(selected_name, first_day_of_month, &days);
+        ledger.save(&config.leave.ledger)?;
+    }
 
     Ok(())
 }
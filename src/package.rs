@@ -0,0 +1,180 @@
+//! Reading the parts of the xlsx package that calamine doesn't expose, e.g. comments and cell styles. The
+//! XML is simple and machine-written, so it's searched as text rather than fully parsed
+
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::Context;
+
+pub type Archive = zip::ZipArchive<File>;
+
+
+pub fn open(path: &Path) -> anyhow::Result<Archive> {
+    let file = File::open(path).context("Could not open spreadsheet")?;
+    zip::ZipArchive::new(file).context("Spreadsheet is not an xlsx file")
+}
+
+/// The path of a sheet's XML part within the package, e.g. "xl/worksheets/sheet1.xml"
+pub fn sheet_path(archive: &mut Archive, sheet: &str) -> anyhow::Result<Option<String>> {
+    let workbook = read_part(archive, "xl/workbook.xml")?.context("Workbook has no xl/workbook.xml")?;
+    let Some(id) = tags(&workbook, "sheet").find(|tag| attribute(tag, "name").as_deref() == Some(sheet)).and_then(|tag| attribute(tag, "r:id")) else {
+        return Ok(None);
+    };
+    relationship_target(archive, "xl/workbook.xml", |tag| attribute(tag, "Id").as_deref() == Some(id.as_str()))
+}
+
+/// The path of the first part related to `part` with a relationship type ending in `kind`, e.g. "/comments"
+pub fn related_part(archive: &mut Archive, part: &str, kind: &str) -> anyhow::Result<Option<String>> {
+    relationship_target(archive, part, |tag| attribute(tag, "Type").is_some_and(|t| t.ends_with(kind)))
+}
+
+/// The contents of a part of the workbook, or None if there's no such part
+pub fn read_part(archive: &mut Archive, name: &str) -> anyhow::Result<Option<String>> {
+    let Ok(mut part) = archive.by_name(name) else {
+        return Ok(None);
+    };
+    let mut xml = String::new();
+    part.read_to_string(&mut xml).with_context(|| format!("Unable to read {name} from spreadsheet"))?;
+    Ok(Some(xml))
+}
+
+/// The path of the first part related to `part` that `matches` its Relationship tag
+fn relationship_target(archive: &mut Archive, part: &str, matches: impl Fn(&str) -> bool) -> anyhow::Result<Option<String>> {
+    let (directory, file_name) = part.rsplit_once('/').unwrap_or(("", part));
+    let Some(rels) = read_part(archive, &format!("{directory}/_rels/{file_name}.rels"))? else {
+        return Ok(None);
+    };
+    let target = tags(&rels, "Relationship").find(|tag| matches(tag)).and_then(|tag| attribute(tag, "Target"));
+    Ok(target.map(|target| resolve(directory, &target)))
+}
+
+/// A target relative to a directory, or relative to the root if it starts with '/'
+fn resolve(directory: &str, target: &str) -> String {
+    let mut path: Vec<&str> = if target.starts_with('/') { Vec::new() } else { directory.split('/').filter(|part| !part.is_empty()).collect() };
+    for part in target.split('/').filter(|part| !part.is_empty()) {
+        match part {
+            ".." => { path.pop(); },
+            "." => {},
+            _ => path.push(part),
+        }
+    }
+    path.join("/")
+}
+
+/// The opening tags named `name`, e.g. `<sheet name="Roster" r:id="rId1"/>`, without the angle brackets
+pub fn tags<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    elements(xml, name).map(|(tag, _)| tag)
+}
+
+/// The opening tag and contents of each element named `name`. Self-closing elements have no contents
+pub fn elements<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut rest = xml;
+    std::iter::from_fn(move || loop {
+        let start = rest.find(&open)?;
+        let after_name = &rest[start + open.len()..];
+        // Skip longer names that start the same way, e.g. <author> when looking for <a>
+        if !after_name.starts_with([' ', '>', '/', '\n', '\r', '\t']) {
+            rest = after_name;
+            continue;
+        }
+        let tag_end = after_name.find('>')?;
+        let tag = after_name[..tag_end].trim();
+        rest = &after_name[tag_end + 1..];
+        if let Some(tag) = tag.strip_suffix('/') {
+            return Some((tag.trim(), ""));
+        }
+        let end = rest.find(&close)?;
+        let contents = &rest[..end];
+        rest = &rest[end + close.len()..];
+        return Some((tag, contents));
+    })
+}
+
+/// The value of an attribute in an opening tag
+pub fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!("{name}=");
+    let mut search = tag;
+    loop {
+        let index = search.find(&pattern)?;
+        // Make sure this isn't the end of a longer attribute name, e.g. "Id" in "authorId"
+        let preceded_by_space = index == 0 || search[..index].ends_with(char::is_whitespace);
+        let value = &search[index + pattern.len()..];
+        search = value;
+        if !preceded_by_space {
+            continue;
+        }
+        let quote = value.chars().next()?;
+        let value = &value[1..];
+        return Some(unescape(&value[..value.find(quote)?]));
+    }
+}
+
+/// Replace XML character references, e.g. "&amp;", with the characters they stand for
+pub fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let character = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            reference => reference.strip_prefix("#x").map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| reference.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                unescaped.push(character);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements_and_tags() {
+        let xml = r#"<cols><col min="1"/></cols><c r="A1" t="s" s="3"><v>0</v></c><c r="B1" s="2"/><c r="C1"><v>1</v></c>"#;
+        let cells: Vec<(&str, &str)> = elements(xml, "c").collect();
+        assert_eq!(cells, vec![(r#"r="A1" t="s" s="3""#, "<v>0</v>"), (r#"r="B1" s="2""#, ""), (r#"r="C1""#, "<v>1</v>")]);
+        assert_eq!(tags(xml, "col").count(), 1);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(attribute(r#"r="A1" t="s" s="3""#, "s").as_deref(), Some("3"));
+        assert_eq!(attribute(r#"r="A1" t="s""#, "s"), None);
+        assert_eq!(attribute(r#"ref="D4" authorId="1""#, "Id"), None);
+        assert_eq!(attribute(r#"Id="rId1" Target='sheet1.xml'"#, "Target").as_deref(), Some("sheet1.xml"));
+        assert_eq!(attribute(r#"name="R&amp;D""#, "name").as_deref(), Some("R&D"));
+    }
+
+    #[test]
+    fn unescaping() {
+        assert_eq!(unescape("Swap &amp; cover&#10;&#x41;&lt;&bogus;"), "Swap & cover\nA<&bogus;");
+    }
+
+    #[test]
+    fn resolving_targets() {
+        assert_eq!(resolve("xl/worksheets", "../comments1.xml"), "xl/comments1.xml");
+        assert_eq!(resolve("xl", "/xl/worksheets/sheet1.xml"), "xl/worksheets/sheet1.xml");
+    }
+}
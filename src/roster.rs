@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use chrono::{Datelike, Days, NaiveDate, NaiveDateTime};
+use serde::Serialize;
 
 // M       0630-1330
 // D1      0800-1530
@@ -12,7 +13,11 @@ use chrono::{Datelike, Days, NaiveDate, NaiveDateTime};
 // A1      1330-2100
 // N       2200-0630
 
-#[derive(Debug)]
+/// Added to the categories of overtime events
+pub const OVERTIME_CATEGORY: &str = "Overtime";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
 pub enum EventType {
     M,
     Mx,
@@ -45,6 +50,9 @@ pub enum EventType {
     Leave,
     Sick,
     DayOff,
+    /// An extra duty worked on top of the roster, with the times of the shift it's marked on. The code is as
+    /// written in the cell, e.g. "OT D4" or "D4*"
+    Overtime { shift: Box<EventType>, code: String },
     Other { name: String, hour_start: u32, minute_start: u32, hour_end: u32, minute_end: u32 },
 }
 impl Display for EventType {
@@ -72,16 +80,75 @@ impl Display for EventType {
             EventType::Leave => write!(f, "Annual leave"),
             EventType::Sick => write!(f, "Sick leave"),
             EventType::DayOff => write!(f, "Day off"),
+            EventType::Overtime { shift, .. } => write!(f, "{shift} (overtime)"),
             EventType::Other { name, .. } => write!(f, "{name}"),
         }
     }
 }
 
+impl EventType {
+    /// The code as it would appear on the roster, e.g. D1T or AL
+    pub fn code(&self) -> String {
+        match self {
+            EventType::M => "M".into(),
+            EventType::Mx => "MX".into(),
+            EventType::Mt => "MT".into(),
+            EventType::D1 => "D1".into(),
+            EventType::D1t => "D1T".into(),
+            EventType::D2 => "D2".into(),
+            EventType::D2t => "D2T".into(),
+            EventType::D3 => "D3".into(),
+            EventType::D3t => "D3T".into(),
+            EventType::D4 => "D4".into(),
+            EventType::D4t => "D4T".into(),
+            EventType::D5 => "D5".into(),
+            EventType::D5t => "D5T".into(),
+            EventType::A => "A".into(),
+            EventType::At => "AT".into(),
+            EventType::A1 => "A1".into(),
+            EventType::A1t => "A1T".into(),
+            EventType::N => "N".into(),
+            EventType::DayInLieu => "DIL".into(),
+            EventType::Leave => "AL".into(),
+            EventType::Sick => "SC".into(),
+            EventType::DayOff => "//".into(),
+            EventType::Overtime { code, .. } => code.clone(),
+            EventType::Other { name, .. } => name.clone(),
+        }
+    }
+
+    /// The shift worked, whether or not it's overtime
+    pub fn base(&self) -> &EventType {
+        match self {
+            EventType::Overtime { shift, .. } => shift,
+            _ => self,
+        }
+    }
+
+    pub fn is_overtime(&self) -> bool {
+        matches!(self, EventType::Overtime { .. })
+    }
+
+    /// How long the event lasts, in hours. Events that finish before they start run past midnight.
+    pub fn duration_hours(&self, summer: bool) -> Option<f64> {
+        let (hour_start, minute_start, hour_end, minute_end) = self.start_and_end_time(summer)?;
+        let start = hour_start * 60 + minute_start;
+        let mut end = hour_end * 60 + minute_end;
+        if end < start {
+            end += 24 * 60;
+        }
+        Some((end - start) as f64 / 60.0)
+    }
+}
+
 impl From<&str> for EventType {
     fn from(s: &str) -> Self {
         if s.is_empty() {
             return EventType::DayOff;
         }
+        if let Some(shift) = overtime_shift(s) {
+            return EventType::Overtime { shift: Box::new(shift), code: s.trim().to_string() };
+        }
         match s.to_lowercase().as_str() {
             "m" => EventType::M,
             "mx" => EventType::Mx,
@@ -111,7 +178,25 @@ impl From<&str> for EventType {
     }
 }
 
+/// The shift in a code marked as overtime, with an OT prefix or suffix or an asterisk, e.g. "OT D4", "N-OT"
+/// or "D4*". Only shifts with known times count, so that other codes that happen to start or end with OT
+/// are left alone
+fn overtime_shift(code: &str) -> Option<EventType> {
+    let code = code.trim();
+    let upper = code.to_uppercase();
+    let separators: &[char] = &[' ', '-', '_', '/'];
+    let base = code.strip_prefix('*').or_else(|| code.strip_suffix('*'))
+        .or_else(|| upper.starts_with("OT").then(|| code.get(2..)).flatten())
+        .or_else(|| upper.ends_with("OT").then(|| code.get(..code.len() - 2)).flatten())?
+        .trim_matches(separators);
+    match EventType::from(base) {
+        EventType::Other { .. } | EventType::Overtime { .. } => None,
+        shift => shift.start_and_end_time(false).is_some().then_some(shift),
+    }
+}
+
 impl EventType {
+    #[allow(clippy::zero_prefixed_literal)]
     pub fn start_and_end_time(&self, summer: bool) -> Option<(u32, u32, u32, u32)> {
         match self {
             EventType::M        => Some((06, 30, 13, 30)),
@@ -136,27 +221,123 @@ impl EventType {
             EventType::Leave    => None,
             EventType::Sick     => None,
             EventType::DayOff   => None,
+            EventType::Overtime { shift, .. } => shift.start_and_end_time(summer),
             EventType::Other { hour_start, minute_start, hour_end, minute_end, .. } => Some((*hour_start, *minute_start, *hour_end, *minute_end)),
             
         }
     }
 }
 
+/// A broad grouping of event types, used for colour-coding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftCategory {
+    Early,
+    Day,
+    Late,
+    Night,
+    Leave,
+    DayOff,
+    Other,
+}
+impl Display for ShiftCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShiftCategory::Early => write!(f, "Early"),
+            ShiftCategory::Day => write!(f, "Day"),
+            ShiftCategory::Late => write!(f, "Late"),
+            ShiftCategory::Night => write!(f, "Night"),
+            ShiftCategory::Leave => write!(f, "Leave"),
+            ShiftCategory::DayOff => write!(f, "Day off"),
+            ShiftCategory::Other => write!(f, "Other"),
+        }
+    }
+}
+impl ShiftCategory {
+    pub const ALL: [ShiftCategory; 7] = [ShiftCategory::Early, ShiftCategory::Day, ShiftCategory::Late, ShiftCategory::Night, ShiftCategory::Leave, ShiftCategory::DayOff, ShiftCategory::Other];
+}
+
+impl EventType {
+    pub fn category(&self) -> ShiftCategory {
+        match self {
+            EventType::M | EventType::Mx | EventType::Mt => ShiftCategory::Early,
+            EventType::D1 | EventType::D1t | EventType::D2 | EventType::D2t | EventType::D3 | EventType::D3t => ShiftCategory::Day,
+            EventType::D4 | EventType::D4t | EventType::D5 | EventType::D5t | EventType::A | EventType::At | EventType::A1 | EventType::A1t => ShiftCategory::Late,
+            EventType::N => ShiftCategory::Night,
+            EventType::DayInLieu | EventType::Leave | EventType::Sick => ShiftCategory::Leave,
+            EventType::DayOff => ShiftCategory::DayOff,
+            EventType::Overtime { shift, .. } => shift.category(),
+            EventType::Other { .. } => ShiftCategory::Other,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum CalendarEvent {
-    Normal { name: String, start: NaiveDateTime, end: NaiveDateTime },
-    AllDay { name: String, date: NaiveDate },
-    MultiDay { name: String, start: NaiveDate, end: NaiveDate },
+    Normal { name: String, start: NaiveDateTime, end: NaiveDateTime, description: Option<String>, categories: Vec<String> },
+    AllDay { name: String, date: NaiveDate, description: Option<String>, categories: Vec<String> },
+    MultiDay { name: String, start: NaiveDate, end: NaiveDate, description: Option<String>, categories: Vec<String> },
 }
+impl CalendarEvent {
+    pub fn name(&self) -> &str {
+        match self {
+            CalendarEvent::Normal { name, .. } | CalendarEvent::AllDay { name, .. } | CalendarEvent::MultiDay { name, .. } => name,
+        }
+    }
 
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            CalendarEvent::Normal { description, .. } | CalendarEvent::AllDay { description, .. } | CalendarEvent::MultiDay { description, .. } => description.as_deref(),
+        }
+    }
 
-pub fn generate_calendar_events(first_day_of_month: NaiveDate, days: Vec<EventType>) -> Vec<CalendarEvent>{
+    pub fn categories(&self) -> &[String] {
+        match self {
+            CalendarEvent::Normal { categories, .. } | CalendarEvent::AllDay { categories, .. } | CalendarEvent::MultiDay { categories, .. } => categories,
+        }
+    }
+
+    /// Add a category, unless the event already has it
+    pub fn add_category(&mut self, category: &str) {
+        let (CalendarEvent::Normal { categories, .. } | CalendarEvent::AllDay { categories, .. } | CalendarEvent::MultiDay { categories, .. }) = self;
+        if !categories.iter().any(|c| c == category) {
+            categories.push(category.to_string());
+        }
+    }
+
+    /// Whether the event falls on a date. Timed events are on the day they start
+    pub fn is_on(&self, date: NaiveDate) -> bool {
+        match self {
+            CalendarEvent::Normal { start, .. } => start.date() == date,
+            CalendarEvent::AllDay { date: event_date, .. } => *event_date == date,
+            CalendarEvent::MultiDay { start, end, .. } => (*start..*end).contains(&date),
+        }
+    }
+
+    /// Add a line to the event's description
+    pub fn append_description(&mut self, line: &str) {
+        let (CalendarEvent::Normal { description, .. } | CalendarEvent::AllDay { description, .. } | CalendarEvent::MultiDay { description, .. }) = self;
+        match description {
+            Some(description) => {
+                description.push('\n');
+                description.push_str(line);
+            },
+            None => *description = Some(line.to_string()),
+        }
+    }
+}
+
+
+/// Is it summer? April - October inclusive
+pub fn is_summer(date: &NaiveDate) -> bool {
+    (4..11).contains(&date.month())
+}
+
+pub fn generate_calendar_events(first_day_of_month: NaiveDate, days: &[EventType]) -> Vec<CalendarEvent>{
     let mut events = Vec::new();
     let mut days_off_or_leave_started = None;
     let mut leave_polluted = false;
 
-    // Is it summer? April - October inclusive
-    let summer = (4..11).contains(&first_day_of_month.month());
+    let summer = is_summer(&first_day_of_month);
 
     for (i, day) in days.iter().enumerate() {
 
@@ -171,10 +352,11 @@ pub fn generate_calendar_events(first_day_of_month: NaiveDate, days: Vec<EventTy
             let mut end = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap().and_hms_opt(hour_end, min_end, 0).unwrap();
 
             // If it's a night shift, we finish on the following day
-            if let EventType::N = day {
+            if let EventType::N = day.base() {
                 end = end.checked_add_days(Days::new(1)).unwrap();
             }
-            events.push(CalendarEvent::Normal { name: day.to_string(), start, end });
+            let categories = if day.is_overtime() { vec![OVERTIME_CATEGORY.to_string()] } else { Vec::new() };
+            events.push(CalendarEvent::Normal { name: day.to_string(), start, end, description: None, categories });
         }
 
         // If it's annual leave, deal with that
@@ -196,7 +378,7 @@ pub fn generate_calendar_events(first_day_of_month: NaiveDate, days: Vec<EventTy
                 }
             }
             else {
-                events.push(CalendarEvent::AllDay { name: day.to_string(), date: first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap() });
+                events.push(CalendarEvent::AllDay { name: day.to_string(), date: first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap(), description: None, categories: Vec::new() });
             }
         }
 
@@ -209,9 +391,9 @@ pub fn generate_calendar_events(first_day_of_month: NaiveDate, days: Vec<EventTy
                 let end_date = first_day_of_month.checked_add_days(Days::new((i - 1) as u64)).unwrap();
                 // If they're the same day, we'll put it as an all-day event. Otherwise, as a multi-day event.
                 if start_date == end_date {
-                    events.push(CalendarEvent::AllDay { name: String::from("Annual leave"), date: start_date });
+                    events.push(CalendarEvent::AllDay { name: String::from("Annual leave"), date: start_date, description: None, categories: Vec::new() });
                 } else {
-                    events.push(CalendarEvent::MultiDay { name: String::from("Annual leave"), start: start_date, end: end_date.checked_add_days(Days::new(1)).unwrap() });
+                    events.push(CalendarEvent::MultiDay { name: String::from("Annual leave"), start: start_date, end: end_date.checked_add_days(Days::new(1)).unwrap(), description: None, categories: Vec::new() });
                 }
             }
             
@@ -224,16 +406,98 @@ pub fn generate_calendar_events(first_day_of_month: NaiveDate, days: Vec<EventTy
         let end_date = first_day_of_month.checked_add_days(Days::new((days.len() - 1) as u64)).unwrap();
         // If they're the same day, we'll put it as an all-day event. Otherwise, as a multi-day event.
         if start_date == end_date {
-            events.push(CalendarEvent::AllDay { name: String::from("Annual leave"), date: start_date });
+            events.push(CalendarEvent::AllDay { name: String::from("Annual leave"), date: start_date, description: None, categories: Vec::new() });
         } else {
-            events.push(CalendarEvent::MultiDay { name: String::from("Annual leave"), start: start_date, end: end_date.checked_add_days(Days::new(1)).unwrap() });
+            events.push(CalendarEvent::MultiDay { name: String::from("Annual leave"), start: start_date, end: end_date.checked_add_days(Days::new(1)).unwrap(), description: None, categories: Vec::new() });
         }
     }
 
     events
 }
 
+/// Group events by the day of the month they fall on. Timed events are listed on the day they start and
+/// multi-day events on every day they cover.
+pub fn events_by_day(first_day_of_month: NaiveDate, num_days: usize, events: &[CalendarEvent]) -> Vec<Vec<&CalendarEvent>> {
+    let mut by_day = vec![Vec::new(); num_days];
+    let day_index = |date: NaiveDate| usize::try_from((date - first_day_of_month).num_days()).ok().filter(|i| *i < num_days);
+    for event in events {
+        match event {
+            CalendarEvent::Normal { start, .. } => {
+                if let Some(i) = day_index(start.date()) {
+                    by_day[i].push(event);
+                }
+            },
+            CalendarEvent::AllDay { date, .. } => {
+                if let Some(i) = day_index(*date) {
+                    by_day[i].push(event);
+                }
+            },
+            CalendarEvent::MultiDay { start, end, .. } => {
+                for date in start.iter_days().take_while(|date| date < end) {
+                    if let Some(i) = day_index(date) {
+                        by_day[i].push(event);
+                    }
+                }
+            },
+        }
+    }
+    by_day
+}
+
 #[test]
 fn test_here() {
     
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// The month the rosters in tests are for. 1 January 2025 is a Wednesday and New Year's Day
+    pub fn january_2025() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()
+    }
+
+    /// Roster codes for the days from 1 January 2025
+    pub fn days(codes: &[&str]) -> Vec<EventType> {
+        codes.iter().map(|code| EventType::from(*code)).collect()
+    }
+
+    /// The events for roster codes for the days from 1 January 2025
+    pub fn events(codes: &[&str]) -> Vec<CalendarEvent> {
+        generate_calendar_events(january_2025(), &days(codes))
+    }
+
+    #[test]
+    fn overtime_codes() {
+        for (code, base) in [("OT D4", "D4"), ("N-OT", "N"), ("D4*", "D4"), ("*N", "N"), ("ot-m", "M")] {
+            let event_type = EventType::from(code);
+            assert!(event_type.is_overtime(), "{code} should be overtime");
+            assert_eq!(event_type.base().code(), base);
+            assert_eq!(event_type.code(), code);
+            assert_eq!(event_type.start_and_end_time(true), EventType::from(base).start_and_end_time(true));
+        }
+        assert_eq!(EventType::from("OT D4").to_string(), "D4 shift (overtime)");
+    }
+
+    #[test]
+    fn codes_that_are_not_overtime() {
+        for code in ["OTS", "DOT", "OT", "*", "SPOT", "OT AL"] {
+            let event_type = EventType::from(code);
+            assert!(!event_type.is_overtime(), "{code} shouldn't be overtime");
+            assert!(matches!(event_type, EventType::Other { .. }), "{code} should be an unknown code");
+        }
+    }
+
+    #[test]
+    fn overtime_nights_finish_the_next_day() {
+        let first_day_of_month = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let events = generate_calendar_events(first_day_of_month, &[EventType::from("N*")]);
+        let [CalendarEvent::Normal { start, end, categories, .. }] = events.as_slice() else {
+            panic!("expected one timed event, got {events:?}");
+        };
+        assert_eq!(start.date(), first_day_of_month);
+        assert_eq!(end.date(), first_day_of_month.succ_opt().unwrap());
+        assert_eq!(categories, &[OVERTIME_CATEGORY]);
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::IsTerminal;
+
+use chrono::{Datelike, Days};
+
+use crate::{args::ShowArgs, roster::{self, EventType, ShiftCategory}, util, xlsx};
+
+const CELL_WIDTH: usize = 11;
+const RESET: &str = "\x1b[0m";
+const NOTE_MARKER: &str = "*";
+
+
+pub fn run(args: &ShowArgs) -> anyhow::Result<()> {
+    let roster = xlsx::open_roster(&args.roster)?;
+    let selected_name_index = util::select_name_from_user(&roster, "Whose roster do you wish to see?");
+    let (selected_row, selected_name) = &roster.names[selected_name_index];
+    let codes = roster.codes_for_row(*selected_row)?;
+    let notes = roster.notes_for_row(*selected_row);
+
+    let colour = !args.no_colour && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+    let first_day_of_month = roster.first_day_of_month;
+    let summer = roster::is_summer(&first_day_of_month);
+
+    println!();
+    let title = format!("{} - {}", first_day_of_month.format("%B %Y"), selected_name);
+    println!("{:^width$}", title, width = (CELL_WIDTH + 1) * 7);
+    let weekdays: Vec<String> = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter().map(|day| format!("{day:<CELL_WIDTH$}")).collect();
+    println!("{}", weekdays.join(" "));
+
+    // Each week is printed as two lines: the date and code, then the times
+    let leading_blanks = first_day_of_month.weekday().num_days_from_monday() as usize;
+    let mut code_line: Vec<String> = vec![" ".repeat(CELL_WIDTH); leading_blanks];
+    let mut time_line: Vec<String> = vec![" ".repeat(CELL_WIDTH); leading_blanks];
+    for (i, code) in codes.iter().enumerate() {
+        let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+        let event_type = EventType::from(code.as_str());
+
+        let times = match (&event_type, event_type.start_and_end_time(summer)) {
+            (EventType::Other { .. }, _) => String::from("????-????"),
+            (_, Some((hour_start, minute_start, hour_end, minute_end))) => format!("{hour_start:02}{minute_start:02}-{hour_end:02}{minute_end:02}"),
+            (_, None) => String::new(),
+        };
+        // Days with notes are marked, and the notes listed below the calendar
+        let marker = if notes[i].is_some() { NOTE_MARKER } else { "" };
+        let date_and_code = format!("{:>2} {}{marker}", date.day(), code);
+
+        code_line.push(paint(&format!("{date_and_code:<CELL_WIDTH$.CELL_WIDTH$}"), event_type.category(), colour));
+        time_line.push(paint(&format!("{times:<CELL_WIDTH$.CELL_WIDTH$}"), event_type.category(), colour));
+
+        if code_line.len() == 7 || i == codes.len() - 1 {
+            println!("{}", code_line.join(" "));
+            println!("{}", time_line.join(" "));
+            code_line.clear();
+            time_line.clear();
+        }
+    }
+
+    let notes: Vec<String> = notes.iter().enumerate()
+        .filter_map(|(i, note)| {
+            let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+            note.map(|note| format!("{}: {}", date.format("%a %-d"), note.replace('\n', " / ")))
+        })
+        .collect();
+    if !notes.is_empty() {
+        println!();
+        println!("Notes:");
+        for note in notes {
+            println!("  {note}");
+        }
+    }
+
+    println!();
+    let legend: Vec<String> = ShiftCategory::ALL.iter().map(|category| paint(&format!(" {category} "), *category, colour)).collect();
+    println!("{}", legend.join(" "));
+    Ok(())
+}
+
+fn paint(text: &str, category: ShiftCategory, colour: bool) -> String {
+    let style = match category {
+        ShiftCategory::Early => "\x1b[30;43m",
+        ShiftCategory::Day => "\x1b[30;42m",
+        ShiftCategory::Late => "\x1b[30;48;5;214m",
+        ShiftCategory::Night => "\x1b[97;44m",
+        ShiftCategory::Leave => "\x1b[97;45m",
+        ShiftCategory::DayOff => "",
+        ShiftCategory::Other => "\x1b[30;47m",
+    };
+    if !colour || style.is_empty() {
+        return text.to_string();
+    }
+    format!("{style}{text}{RESET}")
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use serde::Serialize;
+
+use crate::{args::{StatsArgs, StatsFormat}, config, holidays::{self, Holiday}, roster::{self, EventType}, util, xlsx};
+
+const DATE_FORMAT_STRING: &str = "%d %B";
+
+
+/// Totals for one person's month
+#[derive(Debug, Default, Serialize)]
+pub struct Stats {
+    pub name: String,
+    pub hours_worked: f64,
+    pub duties: u32,
+    pub nights: u32,
+    pub weekend_days_worked: u32,
+    pub bank_holidays_worked: u32,
+    pub annual_leave: u32,
+    pub days_in_lieu: u32,
+    pub sick_days: u32,
+    pub days_off: u32,
+    pub overtime_duties: u32,
+    pub overtime_hours: f64,
+    pub shift_counts: BTreeMap<String, u32>,
+}
+
+pub fn run(args: &StatsArgs) -> anyhow::Result<()> {
+    let config = config::load(args.config.as_deref())?;
+    let roster = xlsx::open_roster(&args.roster)?;
+    let holidays = holidays::load(&config.holidays, roster.first_day_of_month.year())?;
+
+    let people = if args.all {
+        roster.people(args.watch.as_deref())?
+    } else {
+        let selected_name_index = util::select_name_from_user(&roster, "Whose statistics do you wish to see?");
+        vec![roster.names[selected_name_index].clone()]
+    };
+
+    let mut all_stats = Vec::with_capacity(people.len());
+    for (row, name) in &people {
+        let mut days = roster.events_for_row(*row)?;
+        if args.all {
+            warn_unknown_times(name, roster.first_day_of_month, &days);
+        } else {
+            util::get_other_event_times_from_user(roster.first_day_of_month, &mut days);
+        }
+        all_stats.push(calculate(name, roster.first_day_of_month, &days, &holidays));
+    }
+
+    match args.format {
+        StatsFormat::Table => print_table(&all_stats),
+        StatsFormat::Json => println!("{}", serde_json::to_string_pretty(&all_stats)?),
+    }
+    Ok(())
+}
+
+fn warn_unknown_times(name: &str, first_day_of_month: NaiveDate, days: &[EventType]) {
+    for (i, day) in days.iter().enumerate() {
+        if let EventType::Other { name: code, .. } = day {
+            let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+            eprintln!("Warning: not counting hours for '{code}' for {name} on {} as its times are unknown", date.format(DATE_FORMAT_STRING));
+        }
+    }
+}
+
+/// Total up a person's month. Unknown codes whose times haven't been filled in count towards the shift
+/// counts but not towards hours or duties. Overtime counts towards everything, and is also totalled separately.
+pub fn calculate(name: &str, first_day_of_month: NaiveDate, days: &[EventType], holidays: &[Holiday]) -> Stats {
+    let summer = roster::is_summer(&first_day_of_month);
+    let mut stats = Stats { name: name.to_string(), ..Default::default() };
+
+    for (i, day) in days.iter().enumerate() {
+        let date = first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+        *stats.shift_counts.entry(day.code()).or_default() += 1;
+
+        match day {
+            EventType::Leave => stats.annual_leave += 1,
+            EventType::DayInLieu => stats.days_in_lieu += 1,
+            EventType::Sick => stats.sick_days += 1,
+            EventType::DayOff => stats.days_off += 1,
+            _ => (),
+        }
+
+        let hours = match day.duration_hours(summer) {
+            Some(hours) if hours > 0.0 => hours,
+            _ => continue,
+        };
+        stats.hours_worked += hours;
+        stats.duties += 1;
+        if day.is_overtime() {
+            stats.overtime_duties += 1;
+            stats.overtime_hours += hours;
+        }
+        if let EventType::N = day.base() {
+            stats.nights += 1;
+        }
+        if let Weekday::Sat | Weekday::Sun = date.weekday() {
+            stats.weekend_days_worked += 1;
+        }
+        if holidays::find(holidays, date).is_some() {
+            stats.bank_holidays_worked += 1;
+        }
+    }
+    stats
+}
+
+fn print_table(all_stats: &[Stats]) {
+    let name_width = all_stats.iter().map(|stats| stats.name.len()).max().unwrap_or(0).max(4);
+    println!(
+        "{:<name_width$}  {:>6}  {:>6}  {:>6}  {:>8}  {:>8}  {:>4}  {:>4}  {:>4}  {:>4}  {:>8}",
+        "Name", "Hours", "Duties", "Nights", "Weekends", "Bank hol", "AL", "DIL", "Sick", "Off", "OT hours",
+    );
+    for stats in all_stats {
+        println!(
+            "{:<name_width$}  {:>6.2}  {:>6}  {:>6}  {:>8}  {:>8}  {:>4}  {:>4}  {:>4}  {:>4}  {:>8.2}",
+            stats.name, stats.hours_worked, stats.duties, stats.nights, stats.weekend_days_worked, stats.bank_holidays_worked,
+            stats.annual_leave, stats.days_in_lieu, stats.sick_days, stats.days_off, stats.overtime_hours,
+        );
+    }
+
+    println!();
+    println!("Shift counts");
+    for stats in all_stats {
+        let counts: Vec<String> = stats.shift_counts.iter().map(|(code, count)| format!("{code} x{count}")).collect();
+        println!("{:<name_width$}  {}", stats.name, counts.join(", "));
+    }
+}
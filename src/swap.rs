@@ -0,0 +1,131 @@
+//! Find colleagues who could take one of a person's shifts in return for one of theirs
+
+use anyhow::bail;
+use chrono::{Days, NaiveDate};
+
+use crate::{args::SwapArgs, check, config::{self, RuleConfig}, roster::{self, EventType, ShiftCategory}, util, xlsx};
+
+const DATE_FORMAT_STRING: &str = "%a %d %B";
+
+
+/// A possible swap: the colleague works the person's shift on the day they want off, and the person works
+/// the colleague's shift on `their_date`
+struct Swap {
+    colleague: String,
+    their_date: NaiveDate,
+    their_shift: EventType,
+    /// Rule breaches the swap would cause, by whom
+    breaches: Vec<String>,
+}
+
+pub fn run(args: &SwapArgs) -> anyhow::Result<()> {
+    let config = config::load(args.config.as_deref())?;
+    let roster = xlsx::open_roster(&args.roster)?;
+    let first_day_of_month = roster.first_day_of_month;
+
+    let (my_row, my_name) = match &args.name {
+        Some(name) => roster.find_name(name)?,
+        None => roster.names[util::select_name_from_user(&roster, "Whose shift do you wish to swap?")].clone(),
+    };
+    let my_days = roster.events_for_row(my_row)?;
+    let Some(day) = args.date.signed_duration_since(first_day_of_month).num_days().try_into().ok().filter(|day: &usize| *day < my_days.len()) else {
+        bail!("{} is not in the roster for {}", args.date, first_day_of_month.format("%B %Y"));
+    };
+    let my_shift = &my_days[day];
+    if !is_swappable(my_shift) {
+        bail!("{my_name} isn't working a shift on {}", args.date.format(DATE_FORMAT_STRING));
+    }
+
+    let mut swaps = Vec::new();
+    for (row, colleague) in roster.people(args.watch.as_deref())? {
+        if row == my_row {
+            continue;
+        }
+        let their_days = roster.events_for_row(row)?;
+        if !matches!(their_days[day], EventType::DayOff) {
+            continue;
+        }
+        for (their_day, their_shift) in their_days.iter().enumerate() {
+            if !matches!(my_days[their_day], EventType::DayOff) || !is_swappable(their_shift) || their_shift.category() != my_shift.category() {
+                continue;
+            }
+
+            let mut my_new_days = my_days.clone();
+            my_new_days[day] = EventType::DayOff;
+            my_new_days[their_day] = their_shift.clone();
+            let mut their_new_days = their_days.clone();
+            their_new_days[day] = my_shift.clone();
+            their_new_days[their_day] = EventType::DayOff;
+
+            let mut breaches = new_breaches(&config.rules, first_day_of_month, &my_days, &my_new_days, &my_name);
+            breaches.extend(new_breaches(&config.rules, first_day_of_month, &their_days, &their_new_days, &colleague));
+            swaps.push(Swap {
+                colleague: colleague.clone(),
+                their_date: first_day_of_month.checked_add_days(Days::new(their_day as u64)).unwrap(),
+                their_shift: their_shift.clone(),
+                breaches,
+            });
+        }
+    }
+
+    println!("Swaps for {my_name}'s {} on {}", my_shift.code(), args.date.format(DATE_FORMAT_STRING));
+    if swaps.is_empty() {
+        println!("Nobody who is off that day is working a shift of the same kind ({}) on one of your days off.", my_shift.category());
+        return Ok(());
+    }
+    // Swaps that keep within the rules first
+    swaps.sort_by_key(|swap| (!swap.breaches.is_empty(), swap.their_date));
+    for swap in swaps {
+        println!("  {}: you work their {} on {}", swap.colleague, swap.their_shift.code(), swap.their_date.format(DATE_FORMAT_STRING));
+        for breach in swap.breaches {
+            println!("      breaks rules: {breach}");
+        }
+    }
+    Ok(())
+}
+
+/// Shifts with known times that can be given away. Unknown codes aren't offered, as their times aren't known,
+/// and nor is overtime
+fn is_swappable(event_type: &EventType) -> bool {
+    !matches!(event_type, EventType::Other { .. } | EventType::Overtime { .. })
+        && !matches!(event_type.category(), ShiftCategory::Leave | ShiftCategory::DayOff)
+}
+
+/// Rule breaches in the new roster that weren't already in the old one
+fn new_breaches(rules: &RuleConfig, first_day_of_month: NaiveDate, old_days: &[EventType], new_days: &[EventType], name: &str) -> Vec<String> {
+    let describe = |days: &[EventType]| -> Vec<String> {
+        check::check_events(rules, &roster::generate_calendar_events(first_day_of_month, days))
+            .into_iter()
+            .map(|violation| format!("{name}, {}: {}", violation.first_date.format("%d %B"), violation.message))
+            .collect()
+    };
+    let old = describe(old_days);
+    describe(new_days).into_iter().filter(|breach| !old.contains(breach)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roster::tests::{days, january_2025};
+
+    fn breaches(old_codes: &[&str], new_codes: &[&str]) -> Vec<String> {
+        new_breaches(&RuleConfig::default(), january_2025(), &days(old_codes), &days(new_codes), "Alex")
+    }
+
+    #[test]
+    fn swaps_that_break_rules() {
+        // Giving away the D4 on the 3rd for an M on the 2nd leaves 8 hours rest after the D4 on the 1st
+        assert_eq!(breaches(&["D4", "//", "D4"], &["D4", "M", "//"]), ["Alex, 01 January: only 8 hours rest between duties (minimum 11)"]);
+        assert_eq!(
+            breaches(&["M", "M", "M", "M", "M", "M", "//", "M"], &["M", "M", "M", "M", "M", "M", "M", "//"]),
+            ["Alex, 01 January: 7 consecutive duties (maximum 6)"],
+        );
+    }
+
+    #[test]
+    fn swaps_within_rules() {
+        assert!(breaches(&["//", "D4", "//", "//"], &["//", "//", "//", "M"]).is_empty());
+        // A breach already in the roster isn't the swap's doing
+        assert!(breaches(&["D4", "M", "//", "D4"], &["D4", "M", "D4", "//"]).is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+//! Who else is on duty at the same time as the selected person, and calendars covering the whole team
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::{Days, NaiveDate, NaiveDateTime};
+
+use crate::{args::{TeamArgs, TeamFormat}, ics, jcal, roster::{self, CalendarEvent}, util::ReminderAdvance, xcal, xlsx::{self, RosterSheet}};
+
+/// Everyone else's events, alongside their names. Rows that can't be read are left out with a warning
+pub fn colleague_events(roster: &RosterSheet, selected_row: usize) -> Vec<(&String, CalendarEvent)> {
+    let mut colleague_events = Vec::new();
+    for (row, name) in roster.names.iter().filter(|(row, _)| *row != selected_row) {
+        match roster.events_for_row(*row) {
+            Ok(days) => colleague_events.extend(roster::generate_calendar_events(roster.first_day_of_month, &days).into_iter().map(|event| (name, event))),
+            Err(e) => eprintln!("Warning: ignoring {name}'s duties: {e}"),
+        }
+    }
+    colleague_events
+}
+
+/// Add the colleagues whose duties overlap each of the person's timed events to the event's description
+pub fn add_colleagues(roster: &RosterSheet, selected_row: usize, events: &mut [CalendarEvent]) {
+    let colleague_events = colleague_events(roster, selected_row);
+    for event in events {
+        let CalendarEvent::Normal { start, end, .. } = event else {
+            continue;
+        };
+        let (start, end) = (*start, *end);
+        let colleagues: Vec<String> = colleague_events.iter()
+            .filter_map(|(name, colleague_event)| match colleague_event {
+                CalendarEvent::Normal { name: shift, start: other_start, end: other_end, .. }
+                    if other_start < other_end && *other_start < end && start < *other_end => Some(format!("{name} ({shift})")),
+                _ => None,
+            })
+            .collect();
+        if !colleagues.is_empty() {
+            event.append_description(&format!("Also on: {}", colleagues.join(", ")));
+        }
+    }
+}
+
+/// Write a calendar with one event for each shift worked on each day, listing everyone on it
+pub fn run(args: &TeamArgs) -> anyhow::Result<()> {
+    let roster = xlsx::open_roster(&args.roster)?;
+    let people = roster.people(args.watch.as_deref())?;
+    let events = team_events(&roster, &people)?;
+    let (mut calendar, settings) = ics::new_calendar(args.name.clone(), None, ReminderAdvance::MinutesBefore(60));
+    for calendar_event in &events {
+        calendar.add_event(ics::new_event(&settings, calendar_event));
+    }
+    match args.format {
+        TeamFormat::Ics => calendar.save_file(&args.output).context("Unable to save file")?,
+        TeamFormat::Jcal => jcal::save_file(&args.output, &calendar)?,
+        TeamFormat::Xcal => xcal::save_file(&args.output, &calendar)?,
+    }
+    println!("Wrote {} shifts for {} {} to {}", events.len(), people.len(), if people.len() == 1 { "person" } else { "people" }, args.output.display());
+    Ok(())
+}
+
+/// List the people one watch per line, or one person per line if the roster has no watches
+fn describe_by_watch(roster: &RosterSheet, people: &[Person]) -> String {
+    if roster.watches.is_empty() {
+        return people.iter().map(|(_, name)| name.as_str()).collect::<Vec<_>>().join("\n");
+    }
+    let mut by_watch: Vec<(Option<&str>, Vec<&str>)> = Vec::new();
+    for (row, name) in people {
+        let watch = roster.watch_for_row(*row);
+        match by_watch.iter_mut().find(|(w, _)| *w == watch) {
+            Some((_, names)) => names.push(name.as_str()),
+            None => by_watch.push((watch, vec![name.as_str()])),
+        }
+    }
+    by_watch.iter()
+        .map(|(watch, names)| format!("{}: {}", watch.map(xlsx::watch_label).unwrap_or_else(|| String::from("No watch")), names.join(", ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Date, start time and code, so that the team's events come out in order
+type ShiftKey = (NaiveDate, Option<NaiveDateTime>, String);
+
+/// A row from the roster and the name to show, marked if the shift is overtime
+type Person = (usize, String);
+
+/// One event per (day, shift), named e.g. "N: SMITH J, JONES K (overtime)". Overtime is listed with the shift it's
+/// marked on. Leave and days off are left out, and codes without known times become all-day events
+fn team_events(roster: &RosterSheet, people: &[(usize, String)]) -> anyhow::Result<Vec<CalendarEvent>> {
+    let summer = roster::is_summer(&roster.first_day_of_month);
+    let mut shifts: BTreeMap<ShiftKey, (Option<NaiveDateTime>, Vec<Person>)> = BTreeMap::new();
+    for (row, name) in people {
+        for (i, day) in roster.events_for_row(*row)?.iter().enumerate() {
+            let date = roster.first_day_of_month.checked_add_days(Days::new(i as u64)).unwrap();
+            let Some((hour_start, minute_start, hour_end, minute_end)) = day.start_and_end_time(summer) else {
+                continue;
+            };
+            let (start, end) = if (hour_start, minute_start) == (hour_end, minute_end) {
+                (None, None)
+            } else {
+                let start = date.and_hms_opt(hour_start, minute_start, 0).unwrap();
+                let mut end = date.and_hms_opt(hour_end, minute_end, 0).unwrap();
+                if end <= start {
+                    end = end.checked_add_days(Days::new(1)).unwrap();
+                }
+                (Some(start), Some(end))
+            };
+            let name = if day.is_overtime() { format!("{name} (overtime)") } else { name.clone() };
+            shifts.entry((date, start, day.base().code())).or_insert((end, Vec::new())).1.push((*row, name));
+        }
+    }
+
+    Ok(shifts.into_iter().map(|((date, start, code), (end, people))| {
+        let names: Vec<&str> = people.iter().map(|(_, name)| name.as_str()).collect();
+        let name = format!("{code}: {}", names.join(", "));
+        let description = Some(describe_by_watch(roster, &people));
+        match (start, end) {
+            (Some(start), Some(end)) => CalendarEvent::Normal { name, start, end, description, categories: Vec::new() },
+            _ => CalendarEvent::AllDay { name, date, description, categories: Vec::new() },
+        }
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use calamine::{Data, Range};
+
+    use super::*;
+
+    #[test]
+    fn overtime_is_grouped_with_its_shift() {
+        let mut worksheet = Range::new((0, 0), (2, 3));
+        for (row, codes) in [(1, ["SMITH", "D4", "D4", "//"]), (2, ["JONES", "D4*", "N", "//"])] {
+            for (column, code) in codes.iter().enumerate() {
+                worksheet.set_value((row, column as u32), Data::String(code.to_string()));
+            }
+        }
+        let names = vec![(1, String::from("SMITH")), (2, String::from("JONES"))];
+        let roster = RosterSheet {
+            first_day_of_month: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            worksheet,
+            first_day_col: 1,
+            last_day_col: 3,
+            names: names.clone(),
+            watches: Vec::new(),
+            comments: HashMap::new(),
+            fills: Default::default(),
+        };
+        let events = team_events(&roster, &names).unwrap();
+        let names: Vec<&str> = events.iter().map(CalendarEvent::name).collect();
+        assert_eq!(names, ["D4: SMITH, JONES (overtime)", "D4: SMITH", "N: JONES"]);
+    }
+}
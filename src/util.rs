@@ -1,49 +1,99 @@
 use std::{fmt::Display, io::Write};
 
 use anyhow::{bail, Context};
-use chrono::{NaiveDate, Datelike};
+use chrono::{NaiveDate, Datelike, FixedOffset, NaiveDateTime, TimeDelta, Weekday};
 
-use crate::args::Args;
+use crate::{roster::{self, CalendarEvent, EventType}, xlsx::{self, RosterSheet}};
 
 
-pub fn get_first_day_of_month(args: &Args) -> anyhow::Result<NaiveDate> {
+pub fn num_days_in_month(date: &NaiveDate) -> usize {
+    let (_, year) = date.year_ce();
+    let last_day_of_month = NaiveDate::from_ymd_opt(year as i32, date.month() + 1, 1)
+        .unwrap_or(NaiveDate::from_ymd_opt(year as i32 + 1, 1, 1).unwrap())
+        .pred_opt().unwrap();
+    last_day_of_month.day() as usize
+}
 
-    let file_name = args.xlsx_path.file_name().and_then(|os_str| os_str.to_str()).context("Invalid character in input file name")?;
+/// The UTC offset in force in Europe/London at the given local time. BST runs from 0100 GMT on the last Sunday
+/// in March until 0100 GMT on the last Sunday in October. Ambiguous times in October are treated as BST.
+pub fn london_utc_offset(local: &NaiveDateTime) -> FixedOffset {
+    let year = local.year();
+    let bst_start = NaiveDate::from_weekday_of_month_opt(year, 3, Weekday::Sun, 5)
+        .unwrap_or_else(|| NaiveDate::from_weekday_of_month_opt(year, 3, Weekday::Sun, 4).unwrap())
+        .and_hms_opt(1, 0, 0).unwrap();
+    let bst_end = NaiveDate::from_weekday_of_month_opt(year, 10, Weekday::Sun, 5)
+        .unwrap_or_else(|| NaiveDate::from_weekday_of_month_opt(year, 10, Weekday::Sun, 4).unwrap())
+        .and_hms_opt(2, 0, 0).unwrap();
+    let offset_hours = if *local >= bst_start && *local < bst_end { 1 } else { 0 };
+    FixedOffset::east_opt(offset_hours * 3600).unwrap()
+}
 
-    let mut year = args.year.map(|year| year as i32);
-    if year.is_none() {
-        for i in 2000..2100 {
-            let year_string = i.to_string();
-            if file_name.contains(&year_string) {
-                year = Some(i);
-                break;
-            }
+/// List the names, under a heading for each watch, and ask the user to choose one. Returns the index into `roster.names`
+pub fn select_name_from_user(roster: &RosterSheet, prompt: &str) -> usize {
+    let names = &roster.names;
+    let mut current_watch = None;
+    for (i, (row, name)) in names.iter().enumerate() {
+        let watch = roster.watch_for_row(*row);
+        if let Some(label) = watch.filter(|_| watch != current_watch) {
+            println!("{}", xlsx::watch_label(label));
+            current_watch = watch;
         }
+        println!("{:>2}: {}", i + 1, name);
     }
-    let year = year.context("Unable to determine year from file name - try specifying it manually with the -y argument")?;
-
-    let mut month = args.month.map(|month| month as u32);
-    if month.is_none() {
-        for (i, m) in ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"].iter().enumerate() {
-            let i = i as u32 + 1;
-            if file_name.contains(m) {
-                month = Some(i);
-                break;
+    loop {
+        print!("{prompt} Enter a number from 1 - {}: ", names.len());
+        std::io::stdout().flush().unwrap();
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).unwrap();
+        match input.trim().parse::<usize>() {
+            Ok(i) if (1..=names.len()).contains(&i) => break i - 1,
+            _ => {
+                println!("Invalid input.");
+                continue;
             }
-        }
+        };
     }
-    let month = month.context("Unable to determine month from file name - try specifying it manually with the -m argument")?;
+}
 
-    let date = NaiveDate::from_ymd_opt(year, month, 1).context("Unable to determine month / year")?;
-    Ok(date)
+/// Ask the user for the start and end times of any events we don't recognise
+pub fn get_other_event_times_from_user(first_day_of_month: NaiveDate, days: &mut [EventType]) {
+    let mut current_day = first_day_of_month;
+    for day in days.iter_mut() {
+        if let EventType::Other { name, .. } = day {
+            let date = current_day.format("%d %B");
+            
+            let (hour_start, minute_start, hour_end, minute_end) = loop {
+                let (hour_start, minute_start) = get_time_from_user(&format!("When does event '{name}' on {date} start?"));
+                let (hour_end, minute_end) = get_time_from_user(&format!("When does event '{name}' on {date} end?"));
+                if hour_start * 100 + minute_start >= hour_end * 100 + minute_end {
+                    println!("End time must be after the start time.");
+                    continue;
+                }
+                break (hour_start, minute_start, hour_end, minute_end);
+            };
+
+            *day = EventType::Other { name: name.clone(), hour_start, minute_start, hour_end, minute_end };
+            
+        }
+        current_day = current_day.succ_opt().unwrap();
+    }
 }
 
-pub fn num_days_in_month(date: &NaiveDate) -> usize {
-    let (_, year) = date.year_ce();
-    let last_day_of_month = NaiveDate::from_ymd_opt(year as i32, date.month() + 1, 1)
-        .unwrap_or(NaiveDate::from_ymd_opt(year as i32 + 1, 1, 1).unwrap())
-        .pred_opt().unwrap();
-    last_day_of_month.day() as usize
+/// Generate the calendar events for a row. Unknown codes are either asked about or, when going through several
+/// people, left out with a warning.
+pub fn get_calendar_events_for_person(roster: &RosterSheet, row: usize, name: &str, ask_for_times: bool) -> anyhow::Result<Vec<CalendarEvent>> {
+    let mut days = roster.events_for_row(row)?;
+    if ask_for_times {
+        get_other_event_times_from_user(roster.first_day_of_month, &mut days);
+    }
+    let events = roster::generate_calendar_events(roster.first_day_of_month, &days);
+    Ok(events.into_iter().filter(|event| match event {
+        CalendarEvent::Normal { name: event_name, start, end, .. } if start >= end => {
+            eprintln!("Warning: ignoring '{event_name}' for {name} on {} as its times are unknown", start.format("%d %B"));
+            false
+        },
+        _ => true,
+    }).collect())
 }
 
 pub fn get_time_from_user(prompt: &str) -> (u32, u32) {
@@ -76,6 +126,7 @@ pub fn get_time_from_user(prompt: &str) -> (u32, u32) {
     }
 }
 
+#[allow(clippy::enum_variant_names)]
 pub enum ReminderAdvance {
     HoursBefore(u8),
     MinutesBefore(u8),
@@ -92,6 +143,14 @@ impl Display for ReminderAdvance {
     }
 }
 impl ReminderAdvance {
+    pub fn duration(&self) -> TimeDelta {
+        match self {
+            ReminderAdvance::HoursBefore(n) => TimeDelta::hours(*n as i64),
+            ReminderAdvance::MinutesBefore(n) => TimeDelta::minutes(*n as i64),
+            ReminderAdvance::DaysBefore(n) => TimeDelta::days(*n as i64),
+        }
+    }
+
     pub fn new(s: &str) -> anyhow::Result<Self> {
         let mut num = s.to_lowercase();
         let suffix = num.split_off(num.len() - 1);
@@ -0,0 +1,110 @@
+use std::{fs::File, io::{BufWriter, Write}, path::Path};
+
+use anyhow::Context;
+use ics::ICalendar;
+
+use crate::{html::escape, ics::{self as ical, ParsedComponent, ParsedProperty, ValueType}};
+
+const XML_NAMESPACE: &str = "urn:ietf:params:xml:ns:icalendar-2.0";
+
+
+/// Write the calendar as xCal (RFC 6321)
+pub fn save_file(path: impl AsRef<Path>, calendar: &ICalendar) -> anyhow::Result<()> {
+    let calendar = ical::parse_calendar(calendar)?;
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str(&format!("<icalendar xmlns=\"{XML_NAMESPACE}\">\n"));
+    write_component(&mut xml, &calendar, 1);
+    xml.push_str("</icalendar>\n");
+
+    let file = File::create(path).context("Unable to create file")?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(xml.as_bytes()).context("Unable to write file")?;
+    writer.flush().context("Unable to write file")?;
+    Ok(())
+}
+
+fn write_component(xml: &mut String, component: &ParsedComponent, depth: usize) {
+    let name = component.name.to_lowercase();
+    let indent = "  ".repeat(depth);
+    xml.push_str(&format!("{indent}<{name}>\n"));
+
+    if !component.properties.is_empty() {
+        xml.push_str(&format!("{indent}  <properties>\n"));
+        for property in &component.properties {
+            write_property(xml, property, depth + 2);
+        }
+        xml.push_str(&format!("{indent}  </properties>\n"));
+    }
+    if !component.components.is_empty() {
+        xml.push_str(&format!("{indent}  <components>\n"));
+        for subcomponent in &component.components {
+            write_component(xml, subcomponent, depth + 2);
+        }
+        xml.push_str(&format!("{indent}  </components>\n"));
+    }
+
+    xml.push_str(&format!("{indent}</{name}>\n"));
+}
+
+fn write_property(xml: &mut String, property: &ParsedProperty, depth: usize) {
+    let name = property.name.to_lowercase();
+    let indent = "  ".repeat(depth);
+    xml.push_str(&format!("{indent}<{name}>\n"));
+
+    if !property.parameters.is_empty() {
+        xml.push_str(&format!("{indent}  <parameters>\n"));
+        for (key, value) in &property.parameters {
+            let key = key.to_lowercase();
+            xml.push_str(&format!("{indent}    <{key}><text>{}</text></{key}>\n", escape(value)));
+        }
+        xml.push_str(&format!("{indent}  </parameters>\n"));
+    }
+
+    // Each of a property's values gets its own element
+    let value_type = property.value_type.name();
+    for value in &property.values {
+        if let ValueType::Recur = property.value_type {
+            xml.push_str(&format!("{indent}  <{value_type}>\n"));
+            for (key, values) in ical::recur_parts(value) {
+                for value in values {
+                    xml.push_str(&format!("{indent}    <{key}>{}</{key}>\n", escape(&value)));
+                }
+            }
+            xml.push_str(&format!("{indent}  </{value_type}>\n"));
+        } else {
+            xml.push_str(&format!("{indent}  <{value_type}>{}</{value_type}>\n", escape(value)));
+        }
+    }
+
+    xml.push_str(&format!("{indent}</{name}>\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ics::tests::{find, parsed_calendar};
+
+    /// A component of the parsed calendar as xCal, without the indentation
+    fn xml(name: &str) -> String {
+        let mut xml = String::new();
+        write_component(&mut xml, find(&parsed_calendar(), name).unwrap(), 0);
+        xml.lines().map(str::trim).collect()
+    }
+
+    #[test]
+    fn each_value_gets_its_own_element() {
+        let xml = xml("VEVENT");
+        assert!(xml.contains("<categories><text>Overtime</text><text>Changed, late</text></categories>"), "{xml}");
+        assert!(xml.contains("<summary><text>N: SMITH, JONES</text></summary>"), "{xml}");
+        assert!(xml.contains("<dtstart><parameters><tzid><text>Europe/London</text></tzid></parameters><date-time>2025-03-01T22:00:00</date-time></dtstart>"), "{xml}");
+    }
+
+    #[test]
+    fn alarms_and_rules() {
+        assert_eq!(xml("VEVENT").matches("<valarm>").count(), 2);
+        let xml = xml("DAYLIGHT");
+        assert!(xml.contains("<rrule><recur><freq>YEARLY</freq><bymonth>3</bymonth><byday>-1SU</byday></recur></rrule>"), "{xml}");
+    }
+}
@@ -1,20 +1,213 @@
-use std::path::Path;
+use std::{collections::HashMap, fmt::{self, Display}, io::{Read, Seek}, ops::RangeInclusive};
 
 use anyhow::{bail, Context};
 use calamine::{Data, Range, Reader, Xlsx};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
 
-use crate::roster::EventType;
+use crate::{args::RosterArgs, comments, detect, fills, roster::EventType, util};
+
+const ROSTER_SHEET: &str = "Roster";
+/// Cell contents longer than this are cut short in excerpts
+const EXCERPT_CELL_WIDTH: usize = 10;
+
+/// Something in the spreadsheet that isn't as expected, and where it is
+#[derive(Debug)]
+pub enum XlsxError {
+    /// The workbook has no sheet with the expected name
+    MissingSheet { sheet: String, available: Vec<String> },
+    /// The sheet is smaller than any roster
+    SheetTooSmall { sheet: String, rows: usize, columns: usize },
+    /// A cell, or a block of cells, doesn't contain what was expected
+    Cell {
+        sheet: String,
+        /// An A1-style address, e.g. "D4", or a range, e.g. "A1:E10"
+        location: String,
+        found: String,
+        expected: String,
+        /// The cells around the location, with the problem cell in brackets
+        excerpt: String,
+        hint: Option<String>,
+    },
+}
+
+impl XlsxError {
+    /// An error for the cell at `row` and `column` of the roster worksheet
+    pub fn cell(worksheet: &Range<Data>, row: usize, column: usize, expected: impl Into<String>) -> Self {
+        XlsxError::Cell {
+            sheet: ROSTER_SHEET.to_string(),
+            location: cell_address(worksheet, row, column),
+            found: describe_cell(worksheet.get((row, column))),
+            expected: expected.into(),
+            excerpt: excerpt(worksheet, row.saturating_sub(1)..=row + 1, column.saturating_sub(2)..=column + 2, Some((row, column))),
+            hint: None,
+        }
+    }
+
+    /// An error for a block of cells of the roster worksheet
+    pub fn cells(worksheet: &Range<Data>, rows: RangeInclusive<usize>, columns: RangeInclusive<usize>, found: impl Into<String>, expected: impl Into<String>) -> Self {
+        XlsxError::Cell {
+            sheet: ROSTER_SHEET.to_string(),
+            location: format!("{}:{}", cell_address(worksheet, *rows.start(), *columns.start()), cell_address(worksheet, *rows.end(), *columns.end())),
+            found: found.into(),
+            expected: expected.into(),
+            excerpt: excerpt(worksheet, rows, columns, None),
+            hint: None,
+        }
+    }
+
+    /// Add a suggestion of how to fix the problem
+    pub fn hint(mut self, text: &str) -> Self {
+        if let XlsxError::Cell { hint, .. } = &mut self {
+            *hint = Some(text.to_string());
+        }
+        self
+    }
+}
+
+impl Display for XlsxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XlsxError::MissingSheet { sheet, available } => write!(f, "Unable to locate '{sheet}' worksheet. The sheets in this workbook are: {}", available.join(", ")),
+            XlsxError::SheetTooSmall { sheet, rows, columns } => {
+                write!(f, "Worksheet '{sheet}' is too small: it has {rows} rows and {columns} columns, but a roster needs at least 60 rows and 33 columns")
+            },
+            XlsxError::Cell { sheet, location, found, expected, excerpt, hint } => {
+                writeln!(f, "Worksheet '{sheet}', {location}: expected {expected}, but found {found}")?;
+                write!(f, "{excerpt}")?;
+                if let Some(hint) = hint {
+                    write!(f, "\n{hint}")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl std::error::Error for XlsxError {}
+
+
+/// A roster worksheet along with where the days and names are within it
+pub struct RosterSheet {
+    pub first_day_of_month: NaiveDate,
+    pub worksheet: Range<Data>,
+    pub first_day_col: usize,
+    pub last_day_col: usize,
+    pub names: Vec<(usize, String)>,
+    /// The rows of the group headers and the watch each one starts, e.g. "B" for "WATCH B" or "OSS"
+    pub watches: Vec<(usize, String)>,
+    /// Notes attached to cells, by A1-style address
+    pub comments: HashMap<String, String>,
+    pub fills: fills::Fills,
+}
+
+impl RosterSheet {
+    pub fn codes_for_row(&self, row: usize) -> anyhow::Result<Vec<String>> {
+        get_month_codes_for_row(&self.worksheet, row, self.first_day_col, self.last_day_col)
+    }
+
+    pub fn events_for_row(&self, row: usize) -> anyhow::Result<Vec<EventType>> {
+        get_month_events_for_row(&self.worksheet, row, self.first_day_col, self.last_day_col)
+    }
+
+    /// The note attached to each day's cell in a row, if any
+    pub fn notes_for_row(&self, row: usize) -> Vec<Option<&str>> {
+        self.for_days_in_row(row, &self.comments)
+    }
+
+    /// The fill colour of each day's cell in a row, if it has one
+    pub fn fills_for_row(&self, row: usize) -> Vec<Option<&str>> {
+        self.for_days_in_row(row, &self.fills.colours)
+    }
+
+    fn for_days_in_row<'a>(&self, row: usize, by_address: &'a HashMap<String, String>) -> Vec<Option<&'a str>> {
+        (self.first_day_col..=self.last_day_col)
+            .map(|column| by_address.get(&cell_address(&self.worksheet, row, column)).map(String::as_str))
+            .collect()
+    }
+
+    /// How many of the people's day cells have a fill that can't be matched to a colour. Title banners and
+    /// headers don't count
+    pub fn skipped_fills(&self) -> usize {
+        self.names.iter()
+            .flat_map(|(row, _)| (self.first_day_col..=self.last_day_col).map(|column| cell_address(&self.worksheet, *row, column)))
+            .filter(|address| self.fills.skipped.contains(address))
+            .count()
+    }
+
+    /// The watch a row is in, taken from the nearest group header above it
+    pub fn watch_for_row(&self, row: usize) -> Option<&str> {
+        self.watches.iter().rev().find(|(header_row, _)| *header_row < row).map(|(_, watch)| watch.as_str())
+    }
+
+    /// Find a person by name, ignoring case
+    pub fn find_name(&self, name: &str) -> anyhow::Result<(usize, String)> {
+        match self.names.iter().find(|(_, n)| n.eq_ignore_ascii_case(name.trim())) {
+            Some(person) => Ok(person.clone()),
+            None => bail!("{name} is not on the roster"),
+        }
+    }
+
+    /// Everyone on the roster, or only those on `watch` if given
+    pub fn people(&self, watch: Option<&str>) -> anyhow::Result<Vec<(usize, String)>> {
+        let Some(watch) = watch else {
+            return Ok(self.names.clone());
+        };
+        let people: Vec<(usize, String)> = self.names.iter()
+            .filter(|(row, _)| self.watch_for_row(*row).is_some_and(|w| w.eq_ignore_ascii_case(watch)))
+            .cloned()
+            .collect();
+        if people.is_empty() {
+            let watches: Vec<&str> = self.watches.iter().map(|(_, watch)| watch.as_str()).collect();
+            bail!("Nobody found on watch {watch}. The watches in this roster are: {}", watches.join(", "));
+        }
+        Ok(people)
+    }
+}
+
+/// Open the roster spreadsheet and locate the days and names within it
+pub fn open_roster(args: &RosterArgs) -> anyhow::Result<RosterSheet> {
+    let mut workbook: Xlsx<_> = calamine::open_workbook(&args.xlsx_path).context("Could not open spreadsheet")?;
+    let worksheet = get_roster_worksheet(&mut workbook)?;
+    let (header_row, name_column) = find_header_row_and_name_column(&worksheet)?;
+    let (first_day_of_month, confidence) = detect::first_day_of_month(args, &workbook.sheet_names(), &worksheet, header_row)?;
+    let num_of_days_in_month = util::num_days_in_month(&first_day_of_month);
+    let (first_day_col, last_day_col) = find_first_and_last_day_columns(&worksheet, header_row, name_column, num_of_days_in_month)?;
+    if let Some(weekdays) = find_weekday_row(&worksheet, header_row, name_column, first_day_col, last_day_col) {
+        if let Err(e) = check_weekdays(first_day_of_month, &weekdays) {
+            // If the month was given on the command line or found in the file name or the sheet, trust it
+            if confidence.is_none_or(|confidence| confidence >= detect::Confidence::FileName) {
+                eprintln!("Warning: {e}");
+            } else {
+                return Err(e.context("The roster doesn't seem to be for the month detected - specify it with the -m and -y arguments"));
+            }
+        }
+    }
+    let names = enumerate_names(&worksheet, header_row, name_column);
+    let watches = enumerate_watches(&worksheet, header_row, name_column);
+    let comments = comments::read(&args.xlsx_path, ROSTER_SHEET).unwrap_or_else(|e| {
+        eprintln!("Warning: ignoring notes on cells: {e}");
+        HashMap::new()
+    });
+    let fills = fills::read(&args.xlsx_path, ROSTER_SHEET).unwrap_or_else(|e| {
+        eprintln!("Warning: ignoring cell colours: {e}");
+        fills::Fills::default()
+    });
+    Ok(RosterSheet { first_day_of_month, worksheet, first_day_col, last_day_col, names, watches, comments, fills })
+}
 
 
 /// Get roster worksheet
-pub fn get_roster_worksheet(path: impl AsRef<Path>) -> anyhow::Result<Range<Data>> {
-    let mut workbook: Xlsx<_>  = calamine::open_workbook(path).context("Could not open spreadsheet")?;
-    let worksheet = workbook.worksheet_range("Roster").context("Unable to locate 'Roster' worksheet")?;
+pub fn get_roster_worksheet<RS: Read + Seek>(workbook: &mut Xlsx<RS>) -> anyhow::Result<Range<Data>> {
+    let sheet_names = workbook.sheet_names();
+    if !sheet_names.iter().any(|name| name == ROSTER_SHEET) {
+        return Err(XlsxError::MissingSheet { sheet: ROSTER_SHEET.to_string(), available: sheet_names }.into());
+    }
+    let worksheet = workbook.worksheet_range(ROSTER_SHEET).context("Unable to read 'Roster' worksheet")?;
     
     // Check worksheet is sufficiently large to avoid bounds checking in other functions
     let (num_rows, num_columns) = worksheet.get_size();
     if num_rows < 60 || num_columns < 33 {
-        bail!("Worksheet is too small")
+        return Err(XlsxError::SheetTooSmall { sheet: ROSTER_SHEET.to_string(), rows: num_rows, columns: num_columns }.into());
     }
     Ok(worksheet)
 }
@@ -26,62 +219,126 @@ pub fn find_header_row_and_name_column(worksheet: &Range<Data>) -> anyhow::Resul
     for (i, row) in rows.enumerate() {
     // If we've got to 10, there's a problem
     if i == 10 {
-        bail!("Header row not found in worksheet")
+        break;
     }    
         
         // Find name column
-        for j in 0..5 {
-            match &row[j] {
+        for (j, cell) in row.iter().take(5).enumerate() {
+            match cell {
                 Data::String(s) if s == "NAME" => return Ok((i, j)),
                 _ => continue,
             }
         }
     }
-    bail!("Header row not found in worksheet")
+    Err(XlsxError::cells(worksheet, 0..=9, 0..=4, "no NAME heading", "a header row with NAME in one of the first 5 columns").into())
 }
 
 pub fn find_first_and_last_day_columns(worksheet: &Range<Data>, header_row: usize, name_column: usize, num_of_days_in_month: usize) -> anyhow::Result<(usize, usize)> {
+    let header_row_index = header_row;
     let mut rows = worksheet.rows();
     let header_row = rows.nth(header_row).unwrap();
     // Find first day
     let mut first = None;
-    for (i, cell) in (&header_row[(name_column + 1)..(name_column + 6)]).iter().enumerate() {
-        if let Data::Float(1.0) = cell {
+    for (i, cell) in header_row[(name_column + 1)..(name_column + 6)].iter().enumerate() {
+        if header_day(cell) == Some(1) {
             first = Some(name_column + 1 + i);
             break;
         }
     }
-    let first = first.context("Unable to locate days row")?;
+    let Some(first) = first else {
+        return Err(XlsxError::cell(worksheet, header_row_index, name_column + 1, "day 1 in one of the 5 columns after NAME")
+            .hint("The days of the month should be in the same row as NAME")
+            .into());
+    };
 
     // Find last day. All months have >= 28 days
-    if let Some(last) = header_row.get(first + num_of_days_in_month - 1) {
-        if let Data::Float(value) = last {
-            if *value as usize == num_of_days_in_month {
-                return Ok((first, first + num_of_days_in_month - 1));
-            }
-        }
+    let last = first + num_of_days_in_month - 1;
+    if header_row.get(last).and_then(header_day) == Some(num_of_days_in_month) {
+        return Ok((first, last));
     }
     
-    bail!("Unable to locate last day of month in spreadsheet")
+    Err(XlsxError::cell(worksheet, header_row_index, last, format!("day {num_of_days_in_month}, the last day of the month"))
+        .hint("Check the month is right, or specify it with the -m and -y arguments")
+        .into())
+}
+
+/// Find a row of weekday names or abbreviations (Mon Tue or Mo Tu) just above or below the header row. Rows with
+/// something in the name column are people, whose codes can look like weekday letters, e.g. "M" and "S"
+pub fn find_weekday_row(worksheet: &Range<Data>, header_row: usize, name_column: usize, first_day_col: usize, last_day_col: usize) -> Option<Vec<String>> {
+    let candidate_rows = [header_row.checked_sub(1), Some(header_row + 1)];
+    candidate_rows.into_iter().flatten().find_map(|row| {
+        let cells = worksheet.rows().nth(row)?;
+        if !cells.get(name_column).is_none_or(|name| name.to_string().trim().is_empty()) {
+            return None;
+        }
+        let cells = cells.get(first_day_col..=last_day_col)?;
+        let weekdays: Vec<String> = cells.iter().map_while(|cell| match cell {
+            Data::String(value) if weekday_matches(value, None) => Some(value.trim().to_string()),
+            _ => None,
+        }).collect();
+        (weekdays.len() == cells.len()).then_some(weekdays)
+    })
+}
+
+/// Check that the weekdays in the roster fall on the right dates for the month
+pub fn check_weekdays(first_day_of_month: NaiveDate, weekdays: &[String]) -> anyhow::Result<()> {
+    let matches_month = |first_day: NaiveDate| weekdays.iter().enumerate().all(|(i, weekday)| {
+        weekday_matches(weekday, Some(first_day.checked_add_days(Days::new(i as u64)).unwrap().weekday()))
+    });
+    if matches_month(first_day_of_month) {
+        return Ok(());
+    }
+
+    // Suggest nearby months that the weekdays do fit
+    let suggestions: Vec<String> = (-12..=12)
+        .filter_map(|offset: i32| {
+            let first_day = if offset < 0 {
+                first_day_of_month.checked_sub_months(Months::new(offset.unsigned_abs()))
+            } else {
+                first_day_of_month.checked_add_months(Months::new(offset as u32))
+            }?;
+            (util::num_days_in_month(&first_day) == weekdays.len() && matches_month(first_day)).then(|| first_day.format("%B %Y").to_string())
+        })
+        .collect();
+    let mut message = format!(
+        "The weekday row starts '{}', but 1 {} is a {}",
+        weekdays.iter().take(7).cloned().collect::<Vec<_>>().join(" "),
+        first_day_of_month.format("%B %Y"),
+        first_day_of_month.format("%A"),
+    );
+    if !suggestions.is_empty() {
+        message.push_str(&format!(". The weekdays fit {}", suggestions.join(", ")));
+    }
+    bail!(message)
+}
+
+/// Whether the text is a weekday name or an abbreviation of at least two letters, and the given weekday if there
+/// is one. Single letters aren't enough, as they're also roster codes
+fn weekday_matches(text: &str, weekday: Option<Weekday>) -> bool {
+    const NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+    let text = text.trim().to_lowercase();
+    if text.chars().count() < 2 {
+        return false;
+    }
+    let is_prefix = |name: &&str| name.starts_with(&text);
+    match weekday {
+        Some(weekday) => is_prefix(&NAMES[weekday.num_days_from_monday() as usize]),
+        None => NAMES.iter().any(is_prefix),
+    }
 }
 
 pub fn enumerate_names(worksheet: &Range<Data>, header_row: usize, name_column: usize) -> Vec<(usize, String)> {
     let mut names = Vec::new();
     let mut current_row = header_row;
     let last_row_to_check = 60 - header_row;
-    let mut rows = worksheet.rows();
-    rows.nth(header_row);
-    while let Some(row) = rows.next() {
+    for row in worksheet.rows().skip(header_row + 1) {
         current_row += 1;
         if current_row == last_row_to_check + 1 { break; }
 
         if let Data::String(value) = &row[name_column] {
-            if value.is_empty() || value.starts_with("WATCH") {
+            if value.is_empty() || value.starts_with("WATCH") || value.starts_with("OSS") {
                 continue;
             }
-            else if value.starts_with("OSS") {
-                break;
-            }
             else {
                 names.push((current_row, value.clone()));
             }
@@ -91,19 +348,266 @@ pub fn enumerate_names(worksheet: &Range<Data>, header_row: usize, name_column:
     names
 }
 
-pub fn get_month_events_for_row(worksheet: &Range<Data>, row: usize, first_day_col: usize, last_day_col: usize) -> anyhow::Result<Vec<EventType>> {
+/// Find the group headers: "WATCH B" starts watch "B", and "OSS" starts the OSS group
+pub fn enumerate_watches(worksheet: &Range<Data>, header_row: usize, name_column: usize) -> Vec<(usize, String)> {
+    let mut watches = Vec::new();
+    let last_row_to_check = 60 - header_row;
+    for (row, cells) in worksheet.rows().enumerate().take(last_row_to_check + 1).skip(header_row + 1) {
+        if let Data::String(value) = &cells[name_column] {
+            if let Some(watch) = value.strip_prefix("WATCH") {
+                watches.push((row, watch.trim().to_string()));
+            }
+            else if value.starts_with("OSS") {
+                watches.push((row, String::from("OSS")));
+            }
+        }
+    }
+    watches
+}
+
+/// How a watch is shown to the user, e.g. "Watch B"
+pub fn watch_label(watch: &str) -> String {
+    if watch == "OSS" { watch.to_string() } else { format!("Watch {watch}") }
+}
+
+pub fn get_month_codes_for_row(worksheet: &Range<Data>, row: usize, first_day_col: usize, last_day_col: usize) -> anyhow::Result<Vec<String>> {
     let mut vec = Vec::with_capacity(last_day_col - first_day_col + 1);
     let mut rows = worksheet.rows();
-    let row = rows.nth(row).context(format!("Error parsing roster. Unable to locate row {row} in spreadsheet"))?;
-    let days = row.get(first_day_col..=last_day_col).context("Not enough columns in row")?;
-    for day in days {
-        if let Data::String(value) = day {
-            vec.push(EventType::from(value.as_str()));
-        }
-        else {
-            bail!("Non-string data type in roster row");
-        }
+    let cells = rows.nth(row).context(format!("Error parsing roster. Unable to locate row {row} in spreadsheet"))?;
+    let days = cells.get(first_day_col..=last_day_col).context("Not enough columns in row")?;
+    for (i, day) in days.iter().enumerate() {
+        let code = match day {
+            Data::String(value) => value.trim().to_string(),
+            // Empty cells are days off
+            Data::Empty => String::new(),
+            // Numbers are codes too, e.g. 1 for a particular duty
+            Data::Float(value) if value.fract() == 0.0 => format!("{value:.0}"),
+            Data::Float(value) => value.to_string(),
+            Data::Int(value) => value.to_string(),
+            // Anything else is kept as an unknown code, so that one bad cell doesn't stop the rest of the roster being read
+            Data::Bool(_) | Data::DateTime(_) | Data::DateTimeIso(_) | Data::DurationIso(_) | Data::Error(_) => {
+                eprintln!(
+                    "Warning: cell {} contains {} where a roster code was expected. Treating it as an unknown code",
+                    cell_address(worksheet, row, first_day_col + i), describe_cell(Some(day)),
+                );
+                cell_text(day)
+            },
+        };
+        vec.push(code);
     }
     
     Ok(vec)
-}
\ No newline at end of file
+}
+
+/// The A1-style address of a cell, e.g. "C5", given its position within the worksheet's range
+pub fn cell_address(worksheet: &Range<Data>, row: usize, column: usize) -> String {
+    format!("{}{}", column_letters(worksheet, column), row_number(worksheet, row))
+}
+
+/// The letters of a column as shown in the spreadsheet, e.g. "C", given its position within the worksheet's range
+fn column_letters(worksheet: &Range<Data>, column: usize) -> String {
+    let (_, start_column) = worksheet.start().unwrap_or((0, 0));
+    let mut column = start_column as usize + column + 1;
+    let mut letters = String::new();
+    while column > 0 {
+        let remainder = (column - 1) % 26;
+        letters.insert(0, (b'A' + remainder as u8) as char);
+        column = (column - 1) / 26;
+    }
+    letters
+}
+
+/// The number of a row as shown in the spreadsheet, given its position within the worksheet's range
+fn row_number(worksheet: &Range<Data>, row: usize) -> usize {
+    let (start_row, _) = worksheet.start().unwrap_or((0, 0));
+    start_row as usize + row + 1
+}
+
+/// A cell's contents as the user would see them in the spreadsheet
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(date_time) => date_time.as_datetime().map(|date_time| date_time.format("%Y-%m-%d").to_string()).unwrap_or_else(|| cell.to_string()),
+        Data::Bool(value) => String::from(if *value { "TRUE" } else { "FALSE" }),
+        _ => cell.to_string(),
+    }
+}
+
+/// What a cell contains, for error messages, e.g. "'AL'" or "the error #REF!"
+fn describe_cell(cell: Option<&Data>) -> String {
+    match cell {
+        None => String::from("the end of the worksheet"),
+        Some(Data::Empty) => String::from("an empty cell"),
+        Some(Data::String(value)) => format!("'{value}'"),
+        Some(Data::Error(e)) => format!("the error {e}"),
+        Some(cell @ (Data::DateTime(_) | Data::DateTimeIso(_) | Data::DurationIso(_))) => format!("the date or time {}", cell_text(cell)),
+        Some(cell) => cell_text(cell),
+    }
+}
+
+/// A small grid of the cells in `rows` and `columns`, labelled with the row numbers and column letters
+/// used in the spreadsheet. The `marked` cell is put in brackets
+fn excerpt(worksheet: &Range<Data>, rows: RangeInclusive<usize>, columns: RangeInclusive<usize>, marked: Option<(usize, usize)>) -> String {
+    let (height, width) = worksheet.get_size();
+    let rows: Vec<usize> = rows.filter(|row| *row < height).collect();
+    let columns: Vec<usize> = columns.filter(|column| *column < width).collect();
+    let text = |row: usize, column: usize| {
+        let mut text: String = worksheet.get((row, column)).map(cell_text).unwrap_or_default().chars().take(EXCERPT_CELL_WIDTH).collect();
+        if marked == Some((row, column)) {
+            text = format!("[{text}]");
+        }
+        text
+    };
+    let widths: Vec<usize> = columns.iter().map(|column| {
+        rows.iter().map(|row| text(*row, *column).chars().count()).chain([column_letters(worksheet, *column).len()]).max().unwrap_or(0)
+    }).collect();
+    let label_width = rows.iter().map(|row| row_number(worksheet, *row).to_string().len()).max().unwrap_or(0);
+
+    let mut excerpt = format!("{:label_width$} |", "");
+    for (column, width) in columns.iter().zip(&widths) {
+        excerpt.push_str(&format!(" {:<width$} |", column_letters(worksheet, *column)));
+    }
+    for row in &rows {
+        excerpt.push_str(&format!("\n{:>label_width$} |", row_number(worksheet, *row)));
+        for (column, width) in columns.iter().zip(&widths) {
+            excerpt.push_str(&format!(" {:<width$} |", text(*row, *column)));
+        }
+    }
+    excerpt
+}
+
+/// The day of the month in a header cell, whether it's a number, a date or text
+fn header_day(cell: &Data) -> Option<usize> {
+    match cell {
+        Data::Float(value) if value.fract() == 0.0 && *value >= 1.0 => Some(*value as usize),
+        Data::Int(value) if *value >= 1 => Some(*value as usize),
+        Data::DateTime(date_time) => date_time.as_datetime().map(|date_time| date_time.day() as usize),
+        Data::DateTimeIso(value) | Data::String(value) => {
+            let value = value.trim();
+            value.parse().ok()
+                .or_else(|| NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok().map(|date| date.day() as usize))
+        },
+        _ => None,
+    }
+}
+
+pub fn get_month_events_for_row(worksheet: &Range<Data>, row: usize, first_day_col: usize, last_day_col: usize) -> anyhow::Result<Vec<EventType>> {
+    let codes = get_month_codes_for_row(worksheet, row, first_day_col, last_day_col)?;
+    Ok(codes.iter().map(|code| EventType::from(code.as_str())).collect())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sheet with NAME and days 1 to 31 in row 2, and the given rows above and below it
+    fn sheet(above: &[&str], below: &[&str]) -> Range<Data> {
+        let mut worksheet = Range::new((0, 0), (2, 31));
+        worksheet.set_value((1, 0), Data::String(String::from("NAME")));
+        for day in 1..=31 {
+            worksheet.set_value((1, day), Data::Float(day as f64));
+        }
+        for (row, cells) in [(0, above), (2, below)] {
+            for (column, text) in cells.iter().enumerate() {
+                worksheet.set_value((row, column as u32), Data::String(text.to_string()));
+            }
+        }
+        worksheet
+    }
+
+    /// Weekday abbreviations for March 2025, which starts on a Saturday, after an empty name cell
+    fn march_2025(names: [&str; 7]) -> Vec<&str> {
+        [""].into_iter().chain(names.into_iter().cycle().skip(5).take(31)).collect()
+    }
+
+    #[test]
+    fn weekday_rows() {
+        let worksheet = sheet(&march_2025(["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]), &[]);
+        let weekdays = find_weekday_row(&worksheet, 1, 0, 1, 31).unwrap();
+        assert_eq!(weekdays[..3], ["Sat", "Sun", "Mon"]);
+        assert!(check_weekdays(NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(), &weekdays).is_ok());
+        let e = check_weekdays(NaiveDate::from_ymd_opt(2025, 4, 1).unwrap(), &weekdays).unwrap_err();
+        assert!(e.to_string().contains("The weekdays fit March 2025"), "{e}");
+
+        let worksheet = sheet(&[], &march_2025(["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"]));
+        assert!(find_weekday_row(&worksheet, 1, 0, 1, 31).is_some());
+    }
+
+    #[test]
+    fn people_and_letters_are_not_weekday_rows() {
+        // A person working only M and S shifts
+        let mut person = vec!["JONES"];
+        person.extend((1..=31).map(|day| if day % 3 == 0 { "S" } else { "M" }));
+        assert!(find_weekday_row(&sheet(&[], &person), 1, 0, 1, 31).is_none());
+
+        let mut named = march_2025(["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]);
+        named[0] = "DAY";
+        assert!(find_weekday_row(&sheet(&named, &[]), 1, 0, 1, 31).is_none());
+
+        assert!(find_weekday_row(&sheet(&march_2025(["M", "T", "W", "T", "F", "S", "S"]), &[]), 1, 0, 1, 31).is_none());
+        assert!(weekday_matches("Thursday", Some(Weekday::Thu)));
+        assert!(!weekday_matches("Thursday", Some(Weekday::Tue)));
+        assert!(!weekday_matches("M", None));
+    }
+
+    #[test]
+    fn only_peoples_days_count_towards_skipped_fills() {
+        let mut roster = RosterSheet {
+            first_day_of_month: NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+            worksheet: sheet(&["ROSTER MARCH 2025"], &["JONES"]),
+            first_day_col: 1,
+            last_day_col: 31,
+            names: vec![(2, String::from("JONES"))],
+            watches: Vec::new(),
+            comments: HashMap::new(),
+            fills: fills::Fills::default(),
+        };
+        // The title banner, a header cell, two of Jones's days and a cell past the last day
+        roster.fills.skipped = ["A1", "C2", "B3", "AF3", "AG3"].into_iter().map(String::from).collect();
+        assert_eq!(roster.skipped_fills(), 2);
+    }
+
+    #[test]
+    fn cell_addresses() {
+        let worksheet = sheet(&[], &[]);
+        assert_eq!(cell_address(&worksheet, 1, 0), "A2");
+        assert_eq!(cell_address(&worksheet, 2, 25), "Z3");
+        assert_eq!(cell_address(&worksheet, 2, 26), "AA3");
+        assert_eq!(cell_address(&worksheet, 0, 31), "AF1");
+        assert_eq!(cell_address(&worksheet, 0, 701), "ZZ1");
+        assert_eq!(cell_address(&worksheet, 0, 702), "AAA1");
+
+        // Positions are within the worksheet's range, which needn't start at A1
+        let worksheet: Range<Data> = Range::new((4, 2), (5, 30));
+        assert_eq!(cell_address(&worksheet, 0, 0), "C5");
+        assert_eq!(cell_address(&worksheet, 1, 24), "AA6");
+    }
+
+    #[test]
+    fn excerpts_are_truncated_and_mark_the_cell() {
+        let worksheet = sheet(&[], &["Bartholomew Smith", "AL", "OT D4 swapped with Jo"]);
+        // Row 4 is past the end of the sheet, so is left out
+        assert_eq!(excerpt(&worksheet, 1..=3, 0..=2, Some((2, 2))), [
+            "  | A          | B  | C            |",
+            "2 | NAME       | 1  | 2            |",
+            "3 | Bartholome | AL | [OT D4 swap] |",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn header_days() {
+        assert_eq!(header_day(&Data::Float(3.0)), Some(3));
+        assert_eq!(header_day(&Data::Float(3.5)), None);
+        assert_eq!(header_day(&Data::Int(0)), None);
+        assert_eq!(header_day(&Data::String(String::from(" 14 "))), Some(14));
+        assert_eq!(header_day(&Data::DateTimeIso(String::from("2025-03-14T00:00:00"))), Some(14));
+        assert_eq!(header_day(&Data::String(String::from("Fri"))), None);
+    }
+
+    #[test]
+    fn unexpected_cells_are_kept_as_unknown_codes() {
+        let mut worksheet = sheet(&[], &["Alex", "D4", "", "7"]);
+        worksheet.set_value((2, 4), Data::Bool(true));
+        worksheet.set_value((2, 5), Data::Float(2.5));
+        assert_eq!(get_month_codes_for_row(&worksheet, 2, 1, 5).unwrap(), ["D4", "", "7", "TRUE", "2.5"]);
+        assert!(get_month_codes_for_row(&worksheet, 3, 1, 5).is_err());
+    }
+}